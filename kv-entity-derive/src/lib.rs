@@ -1,6 +1,6 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{Data, DeriveInput, Fields, Type, parse_macro_input};
+use syn::{Data, DeriveInput, Fields, Ident, Type, parse_macro_input};
 
 #[proc_macro_derive(KvComponent, attributes(index))]
 pub fn derive_kv_components(input: TokenStream) -> TokenStream {
@@ -8,138 +8,33 @@ pub fn derive_kv_components(input: TokenStream) -> TokenStream {
 
     let struct_name = &input.ident;
     let query_struct_name = format_ident!("__{}Query__", struct_name);
+    let view_query_struct_name = format_ident!("__{}ViewQuery__", struct_name);
 
-    // 解析结构体字段，找出带有 #[index] 属性的字段
-    let indexed_fields = match &input.data {
-        Data::Struct(data_struct) => {
-            match &data_struct.fields {
-                Fields::Named(fields) => {
-                    fields
-                        .named
-                        .iter()
-                        .filter_map(|field| {
-                            // 检查字段是否有 #[index] 属性
-                            let has_index =
-                                field.attrs.iter().any(|attr| attr.path().is_ident("index"));
-
-                            if has_index {
-                                let field_name = field.ident.as_ref()?;
-                                let field_type = &field.ty;
-                                Some((field_name, field_type))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>()
-                }
-                _ => vec![],
-            }
-        }
-        _ => vec![],
+    let (indexed_fields, unique_fields) = match parse_indexed_fields(&input) {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
     };
 
-    let indexed_field_names: Vec<_> = indexed_fields
-        .iter()
-        .map(|(field_name, _)| {
-            let name_str = field_name.to_string();
-            quote! { #name_str }
-        })
-        .collect();
-
-    // 为每个索引字段生成编码函数
-    let encode_functions = indexed_fields.iter().map(|(field_name, field_type)| {
-        let encode_fn_name = format_ident!("encode_{}", field_name);
-        let is_string_type = quote!(#field_type).to_string().contains("String");
-        let is_numeric_type = matches_numeric_type(field_type);
-
-        if is_string_type {
-            quote! {
-                pub fn #encode_fn_name(value: impl Into<String>) -> String {
-                    value.into()
-                }
-            }
-        } else if is_numeric_type {
-            let type_str = quote!(#field_type).to_string();
-            let encoding_logic = generate_numeric_encoding(&type_str);
-            quote! {
-                pub fn #encode_fn_name(value: #field_type) -> String {
-                    #encoding_logic
-                }
-            }
-        } else {
-            quote! {}
-        }
-    });
-
-    // 为每个索引字段生成查询方法
-    let query_methods = indexed_fields.iter().map(|(field_name, field_type)| {
-        let method_name = format_ident!("{}", field_name);
-        // 判断字段类型，生成不同的方法签名
-        let is_string_type = quote!(#field_type).to_string().contains("String");
-        let is_numeric_type = matches_numeric_type(field_type);
-        let encode_fn_name = format_ident!("encode_{}", field_name);
-        let field_name_str = field_name.to_string();
-        if is_string_type {
-            quote! {
-                pub fn #method_name(&mut self, value: impl Into<String>) -> kv_entity::Filter<#struct_name> {
-                    let encoded = #struct_name::#encode_fn_name(value);
-                    kv_entity::Filter::new(self.client.clone(), #field_name_str.to_string(), kv_entity::BoundCondition::Value(encoded))
-                }
-            }
-        } else if is_numeric_type {
-            quote! {
-                pub fn #method_name(&mut self, value: #field_type) -> kv_entity::Filter<#struct_name> {
-                    let encoded = #struct_name::#encode_fn_name(value);
-                    kv_entity::Filter::new(self.client.clone(), #field_name_str.to_string(), kv_entity::BoundCondition::Value(encoded))
-                }
-            }
-        } else {
-            let error_msg = format!(
-                "Field '{}' has type '{}' which is not supported for indexing. Only String and numeric types are supported.",
-                field_name, quote!(#field_type)
-            );
-            quote! {
-                compile_error!(#error_msg);
-            }
-        }
-    });
-
-    // 生成 indexed_fields 方法的代码
-    let indexed_fields_impl = {
-        let field_encodings = indexed_fields.iter().map(|(field_name, field_type)| {
-            let field_name_str = field_name.to_string();
-            let is_string_type = quote!(#field_type).to_string().contains("String");
-            let is_numeric_type = matches_numeric_type(field_type);
-            let encode_fn_name = format_ident!("encode_{}", field_name);
-            if is_string_type {
-                quote! {
-                    result.push((#field_name_str.to_string(), #struct_name::#encode_fn_name(self.#field_name.clone())));
-                }
-            } else if is_numeric_type {
-                quote! {
-                    result.push((#field_name_str.to_string(), #struct_name::#encode_fn_name(self.#field_name)));
-                }
-            } else {
-                quote! {}
-            }
-        });
-
-        quote! {
-            fn indexed_fields(&self) -> Vec<(String, String)> {
-                let mut result = Vec::new();
-                #(#field_encodings)*
-                result
-            }
-
-            fn indexed_field_names() -> Vec<&'static str> {
-                vec![#(#indexed_field_names),*]
-            }
-        }
-    };
+    let indexed_field_names = generate_indexed_field_names(&indexed_fields);
+    let encode_functions = generate_encode_functions(&indexed_fields);
+    let query_methods = generate_query_methods(
+        struct_name,
+        struct_name,
+        &indexed_fields,
+        quote! { kv_entity::Filter },
+    );
+    let view_query_methods = generate_query_methods(
+        struct_name,
+        struct_name,
+        &indexed_fields,
+        quote! { kv_entity::ReadViewFilter },
+    );
+    let indexed_fields_impl = generate_indexed_fields_impl(struct_name, &indexed_fields);
 
     let expanded = quote! {
         impl kv_entity::KvComponent for #struct_name {
             type Query = #query_struct_name;
+            type ViewQuery = #view_query_struct_name;
 
             fn type_path() -> kv_entity::TypePath {
                 kv_entity::TypePath(concat!(module_path!(), "::", stringify!(#struct_name)))
@@ -149,7 +44,15 @@ pub fn derive_kv_components(input: TokenStream) -> TokenStream {
                 #query_struct_name { client }
             }
 
+            fn view_query(client: kv_entity::DB) -> #view_query_struct_name {
+                #view_query_struct_name { client }
+            }
+
             #indexed_fields_impl
+
+            fn unique_field_names() -> Vec<&'static str> {
+                vec![#(#unique_fields),*]
+            }
         }
 
         impl #struct_name {
@@ -164,10 +67,26 @@ pub fn derive_kv_components(input: TokenStream) -> TokenStream {
             #(#query_methods)*
         }
 
+        // 只读视图上的查询器，不公开 `client`，避免绕过视图写入
+        #[allow(dead_code)]
+        pub struct #view_query_struct_name {
+            client: kv_entity::DB,
+        }
+
+        impl #view_query_struct_name {
+            #(#view_query_methods)*
+        }
+
         inventory::submit! {
             kv_entity::ComponentMeta {
                 type_path: concat!(module_path!(), "::", stringify!(#struct_name)),
                 indexed_field_names: || vec![#(#indexed_field_names),*],
+                unique_field_names: || vec![#(#unique_fields),*],
+                indexed_values: |data| {
+                    let value = <#struct_name as ::prost::Message>::decode(data)
+                        .map_err(kv_entity::Error::DeserializationError)?;
+                    Ok(kv_entity::KvComponent::indexed_fields(&value))
+                },
             }
         }
     };
@@ -175,22 +94,386 @@ pub fn derive_kv_components(input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
-#[proc_macro_derive(KvRelation)]
+#[proc_macro_derive(KvRelation, attributes(index, kv_relation))]
 pub fn derive_kv_relation(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let struct_name = &input.ident;
+    let query_struct_name = format_ident!("__{}RelationQuery__", struct_name);
+    let view_query_struct_name = format_ident!("__{}RelationViewQuery__", struct_name);
+
+    let (indexed_fields, unique_fields) = match parse_indexed_fields(&input) {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    if !unique_fields.is_empty() {
+        return syn::Error::new_spanned(
+            struct_name,
+            "#[index(unique)] is not supported on relations",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let RelationAttrs {
+        cardinality,
+        symmetric,
+        delete_policy,
+    } = match parse_relation_attrs(&input) {
+        Ok(attrs) => attrs,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let indexed_field_names = generate_indexed_field_names(&indexed_fields);
+    let encode_functions = generate_encode_functions(&indexed_fields);
+    // 编码函数生成在查询器上，避免与同时派生的 KvComponent 重名
+    let query_methods = generate_query_methods(
+        struct_name,
+        &query_struct_name,
+        &indexed_fields,
+        quote! { kv_entity::RelationFilter },
+    );
+    let view_query_methods = generate_query_methods(
+        struct_name,
+        &query_struct_name,
+        &indexed_fields,
+        quote! { kv_entity::RelationFilter },
+    );
+    let indexed_fields_impl = generate_indexed_fields_impl(&query_struct_name, &indexed_fields);
 
     let expanded = quote! {
         impl kv_entity::KvRelation for #struct_name {
+            type Query = #query_struct_name;
+            type ViewQuery = #view_query_struct_name;
+
             fn type_path() -> kv_entity::TypePath {
                 kv_entity::TypePath(concat!(module_path!(), "::", stringify!(#struct_name)))
             }
+
+            fn cardinality() -> kv_entity::Cardinality {
+                #cardinality
+            }
+
+            fn symmetric() -> bool {
+                #symmetric
+            }
+
+            fn delete_policy() -> kv_entity::DeletePolicy {
+                #delete_policy
+            }
+
+            fn query(client: kv_entity::DB) -> #query_struct_name {
+                #query_struct_name { client }
+            }
+
+            fn view_query(client: kv_entity::DB) -> #view_query_struct_name {
+                #view_query_struct_name { client }
+            }
+
+            #indexed_fields_impl
+        }
+
+        pub struct #query_struct_name {
+            pub client: kv_entity::DB,
+        }
+
+        impl #query_struct_name {
+            #(#encode_functions)*
+
+            #(#query_methods)*
+        }
+
+        // 只读视图上的查询器，不公开 `client`，避免绕过视图写入
+        #[allow(dead_code)]
+        pub struct #view_query_struct_name {
+            client: kv_entity::DB,
+        }
+
+        impl #view_query_struct_name {
+            #(#view_query_methods)*
+        }
+
+        inventory::submit! {
+            kv_entity::RelationMeta {
+                type_path: concat!(module_path!(), "::", stringify!(#struct_name)),
+                cardinality: #cardinality,
+                symmetric: #symmetric,
+                delete_policy: #delete_policy,
+                indexed_field_names: || vec![#(#indexed_field_names),*],
+                indexed_values: |data| {
+                    let value = <#struct_name as ::prost::Message>::decode(data)
+                        .map_err(kv_entity::Error::DeserializationError)?;
+                    Ok(kv_entity::KvRelation::indexed_fields(&value))
+                },
+            }
         }
     };
 
     TokenStream::from(expanded)
 }
 
+type IndexedFields<'a> = Vec<(&'a Ident, &'a Type)>;
+
+// 解析结构体字段，找出带有 #[index] 属性的字段，以及其中带有 #[index(unique)] 的字段
+fn parse_indexed_fields(input: &DeriveInput) -> syn::Result<(IndexedFields<'_>, Vec<String>)> {
+    let mut indexed_fields = Vec::new();
+    let mut unique_fields = Vec::new();
+    if let Data::Struct(syn::DataStruct {
+        fields: Fields::Named(fields),
+        ..
+    }) = &input.data
+    {
+        for field in fields.named.iter() {
+            let Some(attr) = field
+                .attrs
+                .iter()
+                .find(|attr| attr.path().is_ident("index"))
+            else {
+                continue;
+            };
+            let Some(field_name) = field.ident.as_ref() else {
+                continue;
+            };
+            if let syn::Meta::List(_) = &attr.meta {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("unique") {
+                        unique_fields.push(field_name.to_string());
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported index option, expected `unique`"))
+                    }
+                })?;
+            }
+            indexed_fields.push((field_name, &field.ty));
+        }
+    }
+    Ok((indexed_fields, unique_fields))
+}
+
+struct RelationAttrs {
+    cardinality: proc_macro2::TokenStream,
+    symmetric: bool,
+    delete_policy: proc_macro2::TokenStream,
+}
+
+// 解析 #[kv_relation(cardinality = "...", symmetric, on_delete = "...")] 属性
+fn parse_relation_attrs(input: &DeriveInput) -> syn::Result<RelationAttrs> {
+    let mut cardinality = quote! { kv_entity::Cardinality::ManyToMany };
+    let mut directed_cardinality = None;
+    let mut symmetric = false;
+    let mut delete_policy = quote! { kv_entity::DeletePolicy::Detach };
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("kv_relation"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("cardinality") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                cardinality = match value.value().as_str() {
+                    "one_to_one" => quote! { kv_entity::Cardinality::OneToOne },
+                    "one_to_many" => {
+                        directed_cardinality = Some(value.clone());
+                        quote! { kv_entity::Cardinality::OneToMany }
+                    }
+                    "many_to_one" => {
+                        directed_cardinality = Some(value.clone());
+                        quote! { kv_entity::Cardinality::ManyToOne }
+                    }
+                    "many_to_many" => quote! { kv_entity::Cardinality::ManyToMany },
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "expected one of `one_to_one`, `one_to_many`, `many_to_one`, `many_to_many`",
+                        ));
+                    }
+                };
+                Ok(())
+            } else if meta.path.is_ident("symmetric") {
+                symmetric = true;
+                Ok(())
+            } else if meta.path.is_ident("on_delete") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                delete_policy = match value.value().as_str() {
+                    "detach" => quote! { kv_entity::DeletePolicy::Detach },
+                    "cascade" => quote! { kv_entity::DeletePolicy::Cascade },
+                    "restrict" => quote! { kv_entity::DeletePolicy::Restrict },
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "expected one of `detach`, `cascade`, `restrict`",
+                        ));
+                    }
+                };
+                Ok(())
+            } else {
+                Err(meta.error("unsupported kv_relation option"))
+            }
+        })?;
+    }
+    // 对称关系没有方向，只能使用两端相同的基数
+    if let (true, Some(value)) = (symmetric, directed_cardinality) {
+        return Err(syn::Error::new_spanned(
+            value,
+            "symmetric relations only support `one_to_one` or `many_to_many`",
+        ));
+    }
+    Ok(RelationAttrs {
+        cardinality,
+        symmetric,
+        delete_policy,
+    })
+}
+
+fn generate_indexed_field_names(indexed_fields: &IndexedFields) -> Vec<proc_macro2::TokenStream> {
+    indexed_fields
+        .iter()
+        .map(|(field_name, _)| {
+            let name_str = field_name.to_string();
+            quote! { #name_str }
+        })
+        .collect()
+}
+
+// 为每个索引字段生成编码函数
+fn generate_encode_functions(indexed_fields: &IndexedFields) -> Vec<proc_macro2::TokenStream> {
+    indexed_fields
+        .iter()
+        .map(|(field_name, field_type)| {
+            let encode_fn_name = format_ident!("encode_{}", field_name);
+            let is_string_type = quote!(#field_type).to_string().contains("String");
+            let is_numeric_type = matches_numeric_type(field_type);
+
+            if is_string_type {
+                quote! {
+                    pub fn #encode_fn_name(value: impl Into<String>) -> String {
+                        value.into()
+                    }
+                }
+            } else if is_numeric_type {
+                let type_str = quote!(#field_type).to_string();
+                let encoding_logic = generate_numeric_encoding(&type_str);
+                quote! {
+                    pub fn #encode_fn_name(value: #field_type) -> String {
+                        #encoding_logic
+                    }
+                }
+            } else {
+                quote! {}
+            }
+        })
+        .collect()
+}
+
+// 为每个索引字段生成查询方法，`filter` 为查询结果的类型
+fn generate_query_methods(
+    struct_name: &Ident,
+    encoder: &Ident,
+    indexed_fields: &IndexedFields,
+    filter: proc_macro2::TokenStream,
+) -> Vec<proc_macro2::TokenStream> {
+    indexed_fields.iter().map(|(field_name, field_type)| {
+        let method_name = format_ident!("{}", field_name);
+        // 判断字段类型，生成不同的方法签名
+        let is_string_type = quote!(#field_type).to_string().contains("String");
+        let is_numeric_type = matches_numeric_type(field_type);
+        let encode_fn_name = format_ident!("encode_{}", field_name);
+        let field_name_str = field_name.to_string();
+        if is_string_type {
+            let prefix_method_name = format_ident!("{}_prefix", field_name);
+            quote! {
+                pub fn #method_name(&mut self, value: impl Into<String>) -> #filter<#struct_name> {
+                    let encoded = #encoder::#encode_fn_name(value);
+                    #filter::new(self.client.clone(), #field_name_str.to_string(), kv_entity::BoundCondition::Value(encoded))
+                }
+
+                /// 检索以 `prefix` 开头的值
+                pub fn #prefix_method_name(&mut self, prefix: impl Into<String>) -> #filter<#struct_name> {
+                    let encoded = #encoder::#encode_fn_name(prefix);
+                    #filter::new(self.client.clone(), #field_name_str.to_string(), kv_entity::BoundCondition::Prefix(encoded))
+                }
+            }
+        } else if is_numeric_type {
+            let range_method_name = format_ident!("{}_range", field_name);
+            let gt_method_name = format_ident!("{}_gt", field_name);
+            let lt_method_name = format_ident!("{}_lt", field_name);
+            let between_method_name = format_ident!("{}_between", field_name);
+            quote! {
+                pub fn #method_name(&mut self, value: #field_type) -> #filter<#struct_name> {
+                    let encoded = #encoder::#encode_fn_name(value);
+                    #filter::new(self.client.clone(), #field_name_str.to_string(), kv_entity::BoundCondition::Value(encoded))
+                }
+
+                /// 检索落在 `range` 中的值，支持 `a..b`、`a..=b`、`a..`、`..b` 等区间
+                pub fn #range_method_name(&mut self, range: impl ::std::ops::RangeBounds<#field_type>) -> #filter<#struct_name> {
+                    let start = range.start_bound().map(|value| #encoder::#encode_fn_name(*value));
+                    let end = range.end_bound().map(|value| #encoder::#encode_fn_name(*value));
+                    #filter::new(self.client.clone(), #field_name_str.to_string(), kv_entity::BoundCondition::Bounds(start, end))
+                }
+
+                /// 检索大于 `value` 的值
+                pub fn #gt_method_name(&mut self, value: #field_type) -> #filter<#struct_name> {
+                    self.#range_method_name((::std::ops::Bound::Excluded(value), ::std::ops::Bound::Unbounded))
+                }
+
+                /// 检索小于 `value` 的值
+                pub fn #lt_method_name(&mut self, value: #field_type) -> #filter<#struct_name> {
+                    self.#range_method_name(..value)
+                }
+
+                /// 检索 `[start, end]` 之间的值
+                pub fn #between_method_name(&mut self, start: #field_type, end: #field_type) -> #filter<#struct_name> {
+                    self.#range_method_name(start..=end)
+                }
+            }
+        } else {
+            let error_msg = format!(
+                "Field '{}' has type '{}' which is not supported for indexing. Only String and numeric types are supported.",
+                field_name, quote!(#field_type)
+            );
+            quote! {
+                compile_error!(#error_msg);
+            }
+        }
+    }).collect()
+}
+
+// 生成 indexed_fields 方法的代码
+fn generate_indexed_fields_impl(
+    encoder: &Ident,
+    indexed_fields: &IndexedFields,
+) -> proc_macro2::TokenStream {
+    let indexed_field_names = generate_indexed_field_names(indexed_fields);
+    let field_encodings = indexed_fields.iter().map(|(field_name, field_type)| {
+        let field_name_str = field_name.to_string();
+        let is_string_type = quote!(#field_type).to_string().contains("String");
+        let is_numeric_type = matches_numeric_type(field_type);
+        let encode_fn_name = format_ident!("encode_{}", field_name);
+        if is_string_type {
+            quote! {
+                result.push((#field_name_str.to_string(), #encoder::#encode_fn_name(self.#field_name.clone())));
+            }
+        } else if is_numeric_type {
+            quote! {
+                result.push((#field_name_str.to_string(), #encoder::#encode_fn_name(self.#field_name)));
+            }
+        } else {
+            quote! {}
+        }
+    });
+
+    quote! {
+        fn indexed_fields(&self) -> Vec<(String, String)> {
+            let mut result = Vec::new();
+            #(#field_encodings)*
+            result
+        }
+
+        fn indexed_field_names() -> Vec<&'static str> {
+            vec![#(#indexed_field_names),*]
+        }
+    }
+}
+
 // 生成数字类型的编码逻辑
 fn generate_numeric_encoding(type_str: &str) -> proc_macro2::TokenStream {
     match type_str {
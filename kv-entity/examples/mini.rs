@@ -1,8 +1,9 @@
-use futures::StreamExt;
 use futures::TryStreamExt;
 use kv_entity::DB;
 use kv_entity::Error;
+use kv_entity::Order;
 use kv_entity::RelationDirection;
+use kv_entity::With;
 
 #[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct UserInfo {
@@ -14,6 +15,7 @@ pub struct UserInfo {
     #[prost(int32, tag = "2")]
     pub age: i32,
 
+    #[index(unique)]
     #[prost(string, tag = "3")]
     pub email: ::prost::alloc::string::String,
 }
@@ -28,10 +30,25 @@ pub struct UserExtend {
     kv_entity::KvRelation, kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message,
 )]
 pub struct FriendRelation {
+    #[index]
     #[prost(int32, tag = "1")]
     pub favorability: i32,
 }
 
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(symmetric)]
+pub struct TeammateRelation {
+    #[prost(string, tag = "1")]
+    pub team: ::prost::alloc::string::String,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(cardinality = "one_to_many", on_delete = "cascade")]
+pub struct OwnsRelation {
+    #[prost(int64, tag = "1")]
+    pub since: i64,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     env_logger::Builder::from_default_env()
@@ -39,7 +56,14 @@ async fn main() -> Result<(), Error> {
         .filter_module("kv_entity", log::LevelFilter::Debug)
         .init();
 
-    let db = DB::new(vec!["172.20.8.107:2379".to_string()]).await?;
+    // set PD_ENDPOINTS=host:2379,... to run against TiKV, otherwise use the in-memory store
+    let db = match std::env::var("PD_ENDPOINTS") {
+        Ok(endpoints) => DB::new(endpoints.split(',').map(str::to_string).collect()).await?,
+        Err(_) => DB::new_in_memory(),
+    };
+
+    // 组件的索引字段发生变化时重建索引
+    db.sync_indexes().await?;
 
     db.keys().await?;
 
@@ -49,7 +73,7 @@ async fn main() -> Result<(), Error> {
             .attach(UserInfo {
                 name: "Bob".to_string(),
                 age: i,
-                email: "bob@example.com".to_string(),
+                email: format!("bob{}@example.com", i),
             })
             .await?;
     }
@@ -92,11 +116,59 @@ async fn main() -> Result<(), Error> {
         .await?;
     log::info!("attach entity {} success", uid_b);
 
+    // email 带有唯一约束，其他实体不能再使用
+    let result = db
+        .entity(uuid::Uuid::new_v4().to_string())
+        .attach(UserInfo {
+            name: "Fake Alice".to_string(),
+            age: 30,
+            email: "alice@example.com".to_string(),
+        })
+        .await;
+    log::info!("attach duplicate email: {:?}", result.err());
+
+    // insert refuses to overwrite components the entity already has
+    let result = db
+        .entity(uid_b.clone())
+        .insert(UserExtend {
+            extend: "overwrite".to_string(),
+        })
+        .await;
+    log::info!("insert existing extend: {:?}", result.err());
+
+    // timestamps have millisecond precision, step past the current one
+    let before_link = std::time::SystemTime::now();
+    tokio::time::sleep(tokio::time::Duration::from_millis(2)).await;
+
     db.entity(uid_a.clone())
         .link(uid_b.clone(), FriendRelation { favorability: 100 })
         .await?;
     log::info!("link entity {} to {} success", uid_a, uid_b);
 
+    let favorability = db
+        .entity(uid_a.clone())
+        .update_relation::<FriendRelation>(uid_b.clone(), |friend| friend.favorability += 1)
+        .await?
+        .favorability;
+    log::info!("a -> b favorability = {}", favorability);
+
+    // link and attach atomically in one transaction
+    db.transaction(|tx| {
+        let (uid_a, uid_b) = (uid_a.clone(), uid_b.clone());
+        async move {
+            tx.entity(uid_b)
+                .link(uid_a.clone(), FriendRelation { favorability: 80 })
+                .await?;
+            tx.entity(uid_a)
+                .attach(UserExtend {
+                    extend: "friend of bob".to_string(),
+                })
+                .await?;
+            Ok(())
+        }
+    })
+    .await?;
+
     let edges = db
         .entity(uid_b.clone())
         .edges::<FriendRelation>(RelationDirection::In)
@@ -104,6 +176,50 @@ async fn main() -> Result<(), Error> {
         .try_collect::<Vec<_>>()
         .await?;
     log::info!("edges = {:?}", edges);
+    let followers = db
+        .entity(uid_b.clone())
+        .degree::<FriendRelation>(RelationDirection::Out)
+        .await?;
+    log::info!("b is linked by {} friends", followers);
+
+    let close_friends = db
+        .query_relation::<FriendRelation>()
+        .favorability_range(80..)
+        .all()
+        .await?;
+    log::info!("close friends = {:?}", close_friends);
+
+    // symmetric relations are the same edge from either side
+    db.entity(uid_a.clone())
+        .link(
+            uid_b.clone(),
+            TeammateRelation {
+                team: "red".to_string(),
+            },
+        )
+        .await?;
+    let teammate = db
+        .entity(uid_b.clone())
+        .relation::<TeammateRelation>(uid_a.clone())
+        .await?;
+    log::info!("b -> a teammate = {:?}", teammate);
+    let teammates = db
+        .entity(uid_b.clone())
+        .edges_entity::<TeammateRelation>(RelationDirection::In)
+        .await?;
+    log::info!("b teammates = {:?}", teammates);
+    db.entity(uid_b.clone())
+        .unlink::<TeammateRelation>(uid_a.clone())
+        .await?;
+
+    // strict links refuse to point at entities that do not exist
+    let dangling = db
+        .clone()
+        .with_strict_links(true)
+        .entity(uid_a.clone())
+        .link("missing", FriendRelation { favorability: 1 })
+        .await;
+    log::info!("strict link to missing entity = {:?}", dangling.err());
     // or use stream
     // let mut edges = db
     //     .entity(uid_b.clone())
@@ -124,14 +240,139 @@ async fn main() -> Result<(), Error> {
     let b = db
         .query::<UserInfo>()
         .age(25)
-        .entity()
-        .await?
-        .get::<UserExtend>()
-        .await?
-        .unwrap();
+        .with::<UserExtend>()
+        .collect()
+        .await?;
     log::info!("b = {:?}", b);
 
+    // friends of 25-year-olds and their extend data, read from one snapshot
+    let friends = db
+        .query::<UserInfo>()
+        .age(25)
+        .follow::<FriendRelation>(RelationDirection::In)
+        .with::<UserExtend>()
+        .collect()
+        .await?;
+    log::info!("friends = {:?}", friends);
+
+    let adults = db.query::<UserInfo>().age_range(18..).count().await?;
+    let names = db.query::<UserInfo>().name_prefix("Al").all().await?;
+    log::info!("adults = {}, names = {:?}", adults, names);
+
+    let not_alice = db
+        .query::<UserInfo>()
+        .age(25)
+        .and(db.query::<UserInfo>().name("Alice").not())
+        .all()
+        .await?;
+    log::info!("25 but not alice = {:?}", not_alice);
+
+    // oldest first, 3 per page; the cursor string can be handed to an HTTP client
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = db
+            .query::<UserInfo>()
+            .age_range(0..)
+            .order(Order::Desc)
+            .limit(3);
+        if let Some(cursor) = cursor {
+            query = query.after(cursor.parse()?);
+        }
+        let page = query.page().await?;
+        log::info!("page = {:?}", page.items);
+        match page.cursor {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    let mut adults = db.query::<UserInfo>().age_range(18..).stream();
+    while let Some((entity_id, user)) = adults.try_next().await? {
+        log::info!("adult {:?} = {:?}", entity_id, user);
+    }
+
+    let (user, extend) = db
+        .entity(uid_a.clone())
+        .get_many::<(UserInfo, UserExtend)>()
+        .await?;
+    log::info!("a = {:?}, {:?}", user, extend);
+
+    // read-modify-write in one transaction, retried on conflict
+    let user = db
+        .entity(uid_a.clone())
+        .update::<UserInfo>(|user| user.age += 1)
+        .await?;
+    log::info!("a is now {}", user.age);
+
+    // compare-and-set: only write if nobody changed the entity since we read it
+    let revision = db.entity(uid_a.clone()).revision().await?;
+    db.entity(uid_a.clone())
+        .if_revision(revision)
+        .attach(UserExtend {
+            extend: "checked".to_string(),
+        })
+        .await?;
+    let stale = db
+        .entity(uid_a.clone())
+        .if_revision(revision)
+        .detach::<UserExtend>()
+        .await;
+    log::info!("stale write: {:?}", stale.err());
+
+    // users that also have extend data
+    let mut extended = db
+        .query_archetype::<(With<UserInfo>, With<UserExtend>)>()
+        .stream();
+    while let Some((entity_id, (user, extend))) = extended.try_next().await? {
+        log::info!("extended {:?} = {:?}, {:?}", entity_id, user, extend);
+    }
+
+    // everyone reachable within two friendships
+    let mut reachable = db
+        .entity(uid_a.clone())
+        .traverse::<FriendRelation>(RelationDirection::Both)
+        .depth(1..=2)
+        .stream();
+    while let Some((entity_id, depth)) = reachable.try_next().await? {
+        log::info!("reachable {:?} at depth {}", entity_id, depth);
+    }
+    let path = db
+        .shortest_path::<FriendRelation>(uid_a.clone(), uid_b.clone(), 3)
+        .await?;
+    log::info!("path a -> b = {:?}", path);
+
+    // several reads that all see the same snapshot
+    let session = db.read_session().await?;
+    let user = session.entity(uid_a.clone()).get::<UserInfo>().await?;
+    let bobs = session.query::<UserInfo>().name("Bob").count().await?;
+    log::info!("session: a = {:?}, bobs = {}", user, bobs);
+
+    // state as of before the friendship was created
+    let history = db.at_time(before_link);
+    let mut past_friends = history
+        .entity(uid_a.clone())
+        .edges::<FriendRelation>(RelationDirection::Both)
+        .await;
+    while let Some(edge) = past_friends.try_next().await? {
+        log::info!("friend before link: {:?}", edge);
+    }
+
+    // entities owned through a cascading relation are deleted together with the owner
+    let pet = uuid::Uuid::new_v4().to_string();
+    db.entity(pet.clone())
+        .attach(UserExtend {
+            extend: "pet".to_string(),
+        })
+        .await?;
+    db.entity(uid_a.clone())
+        .link(pet.clone(), OwnsRelation { since: 2024 })
+        .await?;
+
     db.entity(uid_a.clone()).delete().await?;
+    log::info!(
+        "pet exists after owner deleted = {}",
+        db.entity(pet).get::<UserExtend>().await?.is_some()
+    );
 
     log::info!("delete entity {} success", uid_a);
 
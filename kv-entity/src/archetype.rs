@@ -0,0 +1,206 @@
+use std::{collections::HashMap, marker::PhantomData, pin::Pin};
+
+use async_stream::try_stream;
+use futures::Stream;
+use prost::Message;
+use tikv_client::{Key, Value};
+
+use crate::{
+    DB, KvComponent, TypePath, component_data_path, db::EntityID, error::Error, key_after,
+    utils::key_to_string,
+};
+
+/// 实体必须拥有组件 `T`，结果中包含该组件的值
+pub struct With<T>(PhantomData<T>);
+
+/// 实体不能拥有组件 `T`，结果中对应位置为 `()`
+pub struct Without<T>(PhantomData<T>);
+
+/// 原型查询中的一项条件
+pub trait ArchetypeTerm {
+    type Item;
+
+    /// 是否要求实体拥有该组件
+    const REQUIRED: bool;
+
+    fn type_path() -> TypePath;
+
+    /// 根据组件数据计算结果，返回 None 表示实体不满足条件
+    fn fetch(data: Option<Value>) -> Result<Option<Self::Item>, Error>;
+}
+
+impl<T> ArchetypeTerm for With<T>
+where
+    T: KvComponent + Message + Default,
+{
+    type Item = T;
+
+    const REQUIRED: bool = true;
+
+    fn type_path() -> TypePath {
+        T::type_path()
+    }
+
+    fn fetch(data: Option<Value>) -> Result<Option<Self::Item>, Error> {
+        data.map(|data| T::decode(data.as_slice()).map_err(Error::DeserializationError))
+            .transpose()
+    }
+}
+
+impl<T> ArchetypeTerm for Without<T>
+where
+    T: KvComponent,
+{
+    type Item = ();
+
+    const REQUIRED: bool = false;
+
+    fn type_path() -> TypePath {
+        T::type_path()
+    }
+
+    fn fetch(data: Option<Value>) -> Result<Option<Self::Item>, Error> {
+        Ok(data.is_none().then_some(()))
+    }
+}
+
+/// 由 [`With`] 和 [`Without`] 组成的元组，描述实体拥有和不拥有的组件
+pub trait Archetype {
+    type Item;
+
+    /// 每一项条件的组件类型和是否要求拥有该组件
+    fn terms() -> Vec<(TypePath, bool)>;
+
+    /// `values` 按条件顺序排列，返回 None 表示实体不满足条件
+    fn fetch(values: Vec<Option<Value>>) -> Result<Option<Self::Item>, Error>;
+}
+
+macro_rules! impl_archetype_for_tuple {
+    ($($T:ident),+) => {
+        impl<$($T),+> Archetype for ($($T,)+)
+        where
+            $($T: ArchetypeTerm, )+
+        {
+            type Item = ($($T::Item,)+);
+
+            fn terms() -> Vec<(TypePath, bool)> {
+                vec![$(($T::type_path(), $T::REQUIRED)),+]
+            }
+
+            fn fetch(values: Vec<Option<Value>>) -> Result<Option<Self::Item>, Error> {
+                let mut values = values.into_iter();
+                Ok(Some(($(
+                    match $T::fetch(values.next().flatten())? {
+                        Some(item) => item,
+                        None => return Ok(None),
+                    },
+                )+)))
+            }
+        }
+    };
+}
+
+impl_archetype_for_tuple!(T1);
+impl_archetype_for_tuple!(T1, T2);
+impl_archetype_for_tuple!(T1, T2, T3);
+impl_archetype_for_tuple!(T1, T2, T3, T4);
+impl_archetype_for_tuple!(T1, T2, T3, T4, T5);
+impl_archetype_for_tuple!(T1, T2, T3, T4, T5, T6);
+impl_archetype_for_tuple!(T1, T2, T3, T4, T5, T6, T7);
+impl_archetype_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+/// 原型查询，按实体 ID 顺序返回满足 `A` 中全部条件的实体及其组件
+pub struct ArchetypeQuery<A> {
+    client: DB,
+    _marker: PhantomData<A>,
+}
+
+impl<A> ArchetypeQuery<A>
+where
+    A: Archetype + 'static,
+    A::Item: Send,
+{
+    pub(crate) fn new(client: DB) -> Self {
+        Self {
+            client,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 以第一个 [`With`] 组件的数据为驱动逐页扫描，每页批量读取其余组件判断是否满足条件
+    ///
+    /// `A` 中没有 [`With`] 时无法确定扫描范围，返回 [`Error::InvalidArchetype`]
+    #[allow(clippy::type_complexity)]
+    pub fn stream(&self) -> Pin<Box<dyn Stream<Item = Result<(EntityID, A::Item), Error>> + Send>> {
+        const PAGE_SIZE: usize = 128;
+
+        let client = self.client.clone();
+        let terms = A::terms();
+
+        Box::pin(try_stream! {
+            let driver = terms.iter().position(|(_, required)| *required).ok_or_else(|| {
+                Error::InvalidArchetype("archetype query requires at least one With<T>".to_string())
+            })?;
+            let mut snapshot = client.snapshot().await?;
+            let driver_type = terms[driver].0;
+
+            let mut start_key: Key = component_data_path(driver_type, &EntityID::Empty).into();
+            let end_key: Key = component_data_path(driver_type, &EntityID::Max).into();
+            loop {
+                let kvs = snapshot
+                    .scan(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
+                    .await?;
+                let Some(last) = kvs.last() else {
+                    break;
+                };
+                start_key = key_after(last.key());
+                let len = kvs.len();
+
+                let mut page = Vec::with_capacity(len);
+                for kv in kvs {
+                    let key = key_to_string(kv.key())?;
+                    let entity_id = EntityID::new_raw(key.rsplit('/').next().ok_or(Error::NotFound)?.to_string());
+                    page.push((entity_id, kv.into_value()));
+                }
+
+                let values = snapshot
+                    .batch_get(
+                        terms
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| *i != driver)
+                            .flat_map(|(_, (type_path, _))| {
+                                page.iter().map(|(id, _)| component_data_path(*type_path, id).into())
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|kv| (kv.key().clone(), kv.into_value()))
+                    .collect::<HashMap<_, _>>();
+
+                for (entity_id, driver_value) in page {
+                    let mut driver_value = Some(driver_value);
+                    let row = terms
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (type_path, _))| {
+                            if i == driver {
+                                driver_value.take()
+                            } else {
+                                values.get(&component_data_path(*type_path, &entity_id).into()).cloned()
+                            }
+                        })
+                        .collect();
+                    if let Some(item) = A::fetch(row)? {
+                        yield (entity_id, item);
+                    }
+                }
+
+                if len < PAGE_SIZE {
+                    break;
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,69 @@
+mod memory;
+mod tikv;
+
+use std::{ops::Range, sync::Arc};
+
+use async_trait::async_trait;
+use tikv_client::{Key, KvPair, Timestamp, Value, proto::kvrpcpb};
+
+use crate::Error;
+
+pub use memory::MemoryBackend;
+pub use tikv::TikvBackend;
+
+/// 与 TiKV 时间戳一致：高位为毫秒级物理时间，低 18 位为逻辑计数
+pub(crate) const PHYSICAL_SHIFT_BITS: u32 = 18;
+
+/// 可以被多个 future 共享使用的事务
+pub(crate) type SharedTransaction = Arc<futures::lock::Mutex<Box<dyn Transaction>>>;
+
+/// 存储后端，负责开启事务和创建快照
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// 开启乐观事务
+    async fn begin_optimistic(&self) -> Result<Box<dyn Transaction>, Error>;
+
+    /// 开启悲观事务
+    async fn begin_pessimistic(&self) -> Result<Box<dyn Transaction>, Error>;
+
+    /// 获取当前时间戳
+    async fn current_timestamp(&self) -> Result<Timestamp, Error>;
+
+    /// 创建指定时间戳上的只读快照
+    fn snapshot(&self, timestamp: Timestamp) -> Box<dyn Snapshot>;
+}
+
+/// 只读快照
+#[async_trait]
+pub trait Snapshot: Send {
+    async fn get(&mut self, key: Key) -> Result<Option<Value>, Error>;
+
+    /// 批量读取，不存在的 key 不会出现在结果中
+    async fn batch_get(&mut self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error>;
+
+    /// 按 key 顺序扫描区间 `[start, end)`，最多返回 `limit` 条
+    async fn scan(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error>;
+
+    async fn scan_keys(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<Key>, Error>;
+
+    /// 按 key 逆序扫描区间 `[start, end)`，最多返回 `limit` 条
+    async fn scan_reverse(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error>;
+}
+
+/// 读写事务，读操作能看到本事务中尚未提交的写入
+#[async_trait]
+pub trait Transaction: Snapshot {
+    /// 读取并锁定 key，提交时若该 key 已被其他事务修改则冲突
+    async fn get_for_update(&mut self, key: Key) -> Result<Option<Value>, Error>;
+
+    async fn put(&mut self, key: Key, value: Value) -> Result<(), Error>;
+
+    async fn delete(&mut self, key: Key) -> Result<(), Error>;
+
+    /// 按顺序应用一组写入，同一个 key 以最后一次写入为准
+    async fn batch_mutate(&mut self, mutations: Vec<kvrpcpb::Mutation>) -> Result<(), Error>;
+
+    async fn commit(&mut self) -> Result<(), Error>;
+
+    async fn rollback(&mut self) -> Result<(), Error>;
+}
@@ -0,0 +1,585 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use tikv_client::{Key, KvPair, Timestamp, TimestampExt, Value, proto::kvrpcpb};
+
+use crate::{
+    Error,
+    backend::{PHYSICAL_SHIFT_BITS, Snapshot, StorageBackend, Transaction},
+};
+
+/// 有序的内存 MVCC 存储，语义上模拟 TiKV 的快照隔离事务，
+/// 用于测试和本地开发，数据不会持久化
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    store: Arc<Mutex<MemoryStore>>,
+}
+
+type VersionEntry<'a> = (&'a Vec<u8>, &'a BTreeMap<u64, Option<Value>>);
+
+#[derive(Default)]
+struct MemoryStore {
+    /// key -> (commit_ts -> value)，value 为 None 表示删除
+    versions: BTreeMap<Vec<u8>, BTreeMap<u64, Option<Value>>>,
+    /// 只加锁未写入的 key 最近一次提交的时间戳，对应 TiKV 中 Lock 类型的写记录，
+    /// 不改变读到的值，只参与冲突检测
+    lock_versions: HashMap<Vec<u8>, u64>,
+    last_ts: u64,
+}
+
+impl MemoryStore {
+    fn next_ts(&mut self) -> u64 {
+        let physical = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.last_ts = (physical << PHYSICAL_SHIFT_BITS).max(self.last_ts + 1);
+        self.last_ts
+    }
+
+    fn read(&self, key: &[u8], ts: u64) -> Option<Value> {
+        self.versions
+            .get(key)?
+            .range(..=ts)
+            .next_back()
+            .and_then(|(_, value)| value.clone())
+    }
+
+    fn latest_commit_ts(&self, key: &[u8]) -> Option<u64> {
+        let written = self
+            .versions
+            .get(key)
+            .and_then(|versions| versions.last_key_value())
+            .map(|(ts, _)| *ts);
+        written.max(self.lock_versions.get(key).copied())
+    }
+
+    /// 起点不小于终点时 `BTreeMap::range` 会 panic，这里按空区间处理
+    fn range(&self, range: &Range<Vec<u8>>) -> impl DoubleEndedIterator<Item = VersionEntry<'_>> {
+        (range.start < range.end)
+            .then(|| self.versions.range(range.clone()))
+            .into_iter()
+            .flatten()
+    }
+
+    fn scan(&self, range: &Range<Vec<u8>>, ts: u64) -> impl Iterator<Item = (Vec<u8>, Value)> {
+        self.range(range).filter_map(move |(key, versions)| {
+            let (_, value) = versions.range(..=ts).next_back()?;
+            Some((key.clone(), value.clone()?))
+        })
+    }
+
+    fn scan_reverse(
+        &self,
+        range: &Range<Vec<u8>>,
+        ts: u64,
+    ) -> impl Iterator<Item = (Vec<u8>, Value)> {
+        self.range(range).rev().filter_map(move |(key, versions)| {
+            let (_, value) = versions.range(..=ts).next_back()?;
+            Some((key.clone(), value.clone()?))
+        })
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StorageBackend for MemoryBackend {
+    async fn begin_optimistic(&self) -> Result<Box<dyn Transaction>, Error> {
+        Ok(Box::new(MemoryTransaction::new(self.store.clone(), false)))
+    }
+
+    async fn begin_pessimistic(&self) -> Result<Box<dyn Transaction>, Error> {
+        Ok(Box::new(MemoryTransaction::new(self.store.clone(), true)))
+    }
+
+    async fn current_timestamp(&self) -> Result<Timestamp, Error> {
+        Ok(Timestamp::from_version(
+            self.store.lock().unwrap().next_ts(),
+        ))
+    }
+
+    fn snapshot(&self, timestamp: Timestamp) -> Box<dyn Snapshot> {
+        Box::new(MemorySnapshot {
+            store: self.store.clone(),
+            ts: timestamp.version(),
+        })
+    }
+}
+
+struct MemorySnapshot {
+    store: Arc<Mutex<MemoryStore>>,
+    ts: u64,
+}
+
+#[async_trait]
+impl Snapshot for MemorySnapshot {
+    async fn get(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        let key: Vec<u8> = key.into();
+        Ok(self.store.lock().unwrap().read(&key, self.ts))
+    }
+
+    async fn batch_get(&mut self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error> {
+        let store = self.store.lock().unwrap();
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                let value = store.read(key.as_ref().into(), self.ts)?;
+                Some(KvPair::new(key, value))
+            })
+            .collect())
+    }
+
+    async fn scan(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        let range = range.start.into()..range.end.into();
+        Ok(self
+            .store
+            .lock()
+            .unwrap()
+            .scan(&range, self.ts)
+            .take(limit as usize)
+            .map(|(key, value)| KvPair::new(key, value))
+            .collect())
+    }
+
+    async fn scan_keys(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<Key>, Error> {
+        Ok(self
+            .scan(range, limit)
+            .await?
+            .into_iter()
+            .map(KvPair::into_key)
+            .collect())
+    }
+
+    async fn scan_reverse(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        let range: Range<Vec<u8>> = range.start.into()..range.end.into();
+        if range.start >= range.end {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .store
+            .lock()
+            .unwrap()
+            .scan_reverse(&range, self.ts)
+            .take(limit as usize)
+            .map(|(key, value)| KvPair::new(key, value))
+            .collect())
+    }
+}
+
+struct MemoryTransaction {
+    store: Arc<Mutex<MemoryStore>>,
+    start_ts: u64,
+    pessimistic: bool,
+    /// 尚未提交的写入，value 为 None 表示删除
+    buffer: BTreeMap<Vec<u8>, Option<Value>>,
+    /// 被锁定的 key 及其冲突检测的起始时间戳
+    locks: HashMap<Vec<u8>, u64>,
+}
+
+impl MemoryTransaction {
+    fn new(store: Arc<Mutex<MemoryStore>>, pessimistic: bool) -> Self {
+        let start_ts = store.lock().unwrap().next_ts();
+        Self {
+            store,
+            start_ts,
+            pessimistic,
+            buffer: BTreeMap::new(),
+            locks: HashMap::new(),
+        }
+    }
+
+    /// 悲观事务在写入或加锁时以当前时间戳检测冲突，乐观事务统一使用 start_ts
+    fn lock_key(&mut self, key: &[u8]) {
+        if self.locks.contains_key(key) {
+            return;
+        }
+        let ts = if self.pessimistic {
+            self.store.lock().unwrap().next_ts()
+        } else {
+            self.start_ts
+        };
+        self.locks.insert(key.to_vec(), ts);
+    }
+
+    fn write(&mut self, key: Vec<u8>, value: Option<Value>) {
+        self.lock_key(&key);
+        self.buffer.insert(key, value);
+    }
+
+    fn read(&self, key: &[u8]) -> Option<Value> {
+        match self.buffer.get(key) {
+            Some(value) => value.clone(),
+            None => self.store.lock().unwrap().read(key, self.start_ts),
+        }
+    }
+}
+
+#[async_trait]
+impl Snapshot for MemoryTransaction {
+    async fn get(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        let key: Vec<u8> = key.into();
+        Ok(self.read(&key))
+    }
+
+    async fn batch_get(&mut self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error> {
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                let value = self.read(key.as_ref().into())?;
+                Some(KvPair::new(key, value))
+            })
+            .collect())
+    }
+
+    async fn scan(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        let range: Range<Vec<u8>> = range.start.into()..range.end.into();
+        if range.start >= range.end {
+            return Ok(Vec::new());
+        }
+        // 缓冲区中的每个删除最多遮挡一条已提交数据，多取这么多条即可保证结果足够
+        let buffered = self.buffer.range(range.clone()).count();
+        let mut merged: BTreeMap<Vec<u8>, Option<Value>> = self
+            .store
+            .lock()
+            .unwrap()
+            .scan(&range, self.start_ts)
+            .take(limit as usize + buffered)
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        for (key, value) in self.buffer.range(range) {
+            merged.insert(key.clone(), value.clone());
+        }
+        Ok(merged
+            .into_iter()
+            .filter_map(|(key, value)| Some(KvPair::new(key, value?)))
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn scan_keys(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<Key>, Error> {
+        Ok(self
+            .scan(range, limit)
+            .await?
+            .into_iter()
+            .map(KvPair::into_key)
+            .collect())
+    }
+
+    async fn scan_reverse(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        let range: Range<Vec<u8>> = range.start.into()..range.end.into();
+        if range.start >= range.end {
+            return Ok(Vec::new());
+        }
+        let buffered = self.buffer.range(range.clone()).count();
+        let mut merged: BTreeMap<Vec<u8>, Option<Value>> = self
+            .store
+            .lock()
+            .unwrap()
+            .scan_reverse(&range, self.start_ts)
+            .take(limit as usize + buffered)
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        for (key, value) in self.buffer.range(range) {
+            merged.insert(key.clone(), value.clone());
+        }
+        Ok(merged
+            .into_iter()
+            .rev()
+            .filter_map(|(key, value)| Some(KvPair::new(key, value?)))
+            .take(limit as usize)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Transaction for MemoryTransaction {
+    async fn get_for_update(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        let key: Vec<u8> = key.into();
+        self.lock_key(&key);
+        if self.pessimistic && !self.buffer.contains_key(&key) {
+            return Ok(self.store.lock().unwrap().read(&key, self.locks[&key]));
+        }
+        Ok(self.read(&key))
+    }
+
+    async fn put(&mut self, key: Key, value: Value) -> Result<(), Error> {
+        self.write(key.into(), Some(value));
+        Ok(())
+    }
+
+    async fn delete(&mut self, key: Key) -> Result<(), Error> {
+        self.write(key.into(), None);
+        Ok(())
+    }
+
+    async fn batch_mutate(&mut self, mutations: Vec<kvrpcpb::Mutation>) -> Result<(), Error> {
+        for mutation in mutations {
+            match mutation.op() {
+                kvrpcpb::Op::Put | kvrpcpb::Op::Insert => {
+                    self.write(mutation.key, Some(mutation.value))
+                }
+                kvrpcpb::Op::Del => self.write(mutation.key, None),
+                kvrpcpb::Op::Lock => self.lock_key(&mutation.key),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        let mut store = self.store.lock().unwrap();
+        for (key, ts) in self.locks.iter() {
+            if store
+                .latest_commit_ts(key)
+                .is_some_and(|commit_ts| commit_ts > *ts)
+            {
+                let key = String::from_utf8_lossy(key).into_owned();
+                self.buffer.clear();
+                self.locks.clear();
+                return Err(Error::WriteConflict(key));
+            }
+        }
+        let commit_ts = store.next_ts();
+        for key in self.locks.keys() {
+            if !self.buffer.contains_key(key) {
+                store.lock_versions.insert(key.clone(), commit_ts);
+            }
+        }
+        for (key, value) in std::mem::take(&mut self.buffer) {
+            store
+                .versions
+                .entry(key)
+                .or_default()
+                .insert(commit_ts, value);
+        }
+        self.locks.clear();
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        self.buffer.clear();
+        self.locks.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> Key {
+        key.to_string().into()
+    }
+
+    async fn put(backend: &MemoryBackend, pairs: &[(&str, &str)]) {
+        let mut txn = backend.begin_optimistic().await.unwrap();
+        for (k, v) in pairs {
+            txn.put(key(k), v.as_bytes().to_vec()).await.unwrap();
+        }
+        txn.commit().await.unwrap();
+    }
+
+    fn keys(pairs: Vec<KvPair>) -> Vec<String> {
+        pairs
+            .into_iter()
+            .map(|pair| String::from_utf8(pair.into_key().into()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn transaction_reads_its_start_snapshot() {
+        let backend = MemoryBackend::new();
+        put(&backend, &[("a", "1")]).await;
+
+        let mut reader = backend.begin_optimistic().await.unwrap();
+        put(&backend, &[("a", "2")]).await;
+
+        assert_eq!(reader.get(key("a")).await.unwrap(), Some(b"1".to_vec()));
+        reader.put(key("a"), b"3".to_vec()).await.unwrap();
+        assert_eq!(reader.get(key("a")).await.unwrap(), Some(b"3".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn snapshot_reads_historical_version() {
+        let backend = MemoryBackend::new();
+        put(&backend, &[("a", "1")]).await;
+        let before = backend.current_timestamp().await.unwrap();
+        put(&backend, &[("a", "2")]).await;
+
+        let mut snapshot = backend.snapshot(before);
+        assert_eq!(snapshot.get(key("a")).await.unwrap(), Some(b"1".to_vec()));
+        let now = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(now);
+        assert_eq!(snapshot.get(key("a")).await.unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes_to_same_key_conflict() {
+        let backend = MemoryBackend::new();
+        let mut first = backend.begin_optimistic().await.unwrap();
+        let mut second = backend.begin_optimistic().await.unwrap();
+        first.put(key("a"), b"1".to_vec()).await.unwrap();
+        second.put(key("a"), b"2".to_vec()).await.unwrap();
+
+        first.commit().await.unwrap();
+        let error = second.commit().await.unwrap_err();
+        assert!(matches!(error, Error::WriteConflict(_)));
+        assert!(error.is_retryable());
+
+        let ts = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(ts);
+        assert_eq!(snapshot.get(key("a")).await.unwrap(), Some(b"1".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn writes_to_different_keys_do_not_conflict() {
+        let backend = MemoryBackend::new();
+        let mut first = backend.begin_optimistic().await.unwrap();
+        let mut second = backend.begin_optimistic().await.unwrap();
+        first.put(key("a"), b"1".to_vec()).await.unwrap();
+        second.put(key("b"), b"2".to_vec()).await.unwrap();
+        first.commit().await.unwrap();
+        second.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn committed_lock_conflicts_with_concurrent_write() {
+        let backend = MemoryBackend::new();
+        put(&backend, &[("a", "1")]).await;
+
+        // 只加锁的事务先提交，并发删除该 key 的事务冲突
+        let mut locker = backend.begin_optimistic().await.unwrap();
+        let mut writer = backend.begin_optimistic().await.unwrap();
+        assert_eq!(
+            locker.get_for_update(key("a")).await.unwrap(),
+            Some(b"1".to_vec())
+        );
+        locker.put(key("b"), b"1".to_vec()).await.unwrap();
+        writer.delete(key("a")).await.unwrap();
+        locker.commit().await.unwrap();
+        assert!(matches!(
+            writer.commit().await,
+            Err(Error::WriteConflict(_))
+        ));
+
+        // 通过 batch_mutate 加的锁同样生效
+        let mut locker = backend.begin_optimistic().await.unwrap();
+        let mut writer = backend.begin_optimistic().await.unwrap();
+        let mut lock = kvrpcpb::Mutation::default();
+        lock.set_op(kvrpcpb::Op::Lock);
+        lock.key = key("a").into();
+        locker.batch_mutate(vec![lock]).await.unwrap();
+        writer.delete(key("a")).await.unwrap();
+        locker.commit().await.unwrap();
+        assert!(matches!(
+            writer.commit().await,
+            Err(Error::WriteConflict(_))
+        ));
+
+        // 加锁不改变读到的值，之后开始的事务不受影响
+        let ts = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(ts);
+        assert_eq!(snapshot.get(key("a")).await.unwrap(), Some(b"1".to_vec()));
+        let mut writer = backend.begin_optimistic().await.unwrap();
+        writer.delete(key("a")).await.unwrap();
+        writer.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rollback_discards_writes() {
+        let backend = MemoryBackend::new();
+        put(&backend, &[("a", "1")]).await;
+
+        let mut txn = backend.begin_optimistic().await.unwrap();
+        txn.put(key("a"), b"2".to_vec()).await.unwrap();
+        txn.delete(key("a")).await.unwrap();
+        txn.put(key("b"), b"2".to_vec()).await.unwrap();
+        txn.rollback().await.unwrap();
+
+        let ts = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(ts);
+        assert_eq!(snapshot.get(key("a")).await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(snapshot.get(key("b")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn scan_is_ordered_limited_and_merges_buffer() {
+        let backend = MemoryBackend::new();
+        put(&backend, &[("a", "1"), ("b", "1"), ("c", "1"), ("d", "1")]).await;
+
+        let ts = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(ts);
+        assert_eq!(
+            keys(snapshot.scan(key("b")..key("z"), 2).await.unwrap()),
+            ["b", "c"]
+        );
+
+        let mut txn = backend.begin_optimistic().await.unwrap();
+        txn.delete(key("b")).await.unwrap();
+        txn.put(key("bb"), b"1".to_vec()).await.unwrap();
+        assert_eq!(
+            keys(txn.scan(key("a")..key("d"), 10).await.unwrap()),
+            ["a", "bb", "c"]
+        );
+        assert_eq!(
+            keys(txn.scan(key("a")..key("z"), 2).await.unwrap()),
+            ["a", "bb"]
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_reverse_is_descending_and_merges_buffer() {
+        let backend = MemoryBackend::new();
+        put(&backend, &[("a", "1"), ("b", "1"), ("c", "1"), ("d", "1")]).await;
+
+        let ts = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(ts);
+        assert_eq!(
+            keys(snapshot.scan_reverse(key("a")..key("d"), 2).await.unwrap()),
+            ["c", "b"]
+        );
+
+        let mut txn = backend.begin_optimistic().await.unwrap();
+        txn.delete(key("c")).await.unwrap();
+        txn.put(key("bb"), b"1".to_vec()).await.unwrap();
+        assert_eq!(
+            keys(txn.scan_reverse(key("a")..key("z"), 3).await.unwrap()),
+            ["d", "bb", "b"]
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_with_inverted_range_is_empty() {
+        let backend = MemoryBackend::new();
+        let mut txn = backend.begin_optimistic().await.unwrap();
+        txn.put(key("b"), b"1".to_vec()).await.unwrap();
+        txn.commit().await.unwrap();
+
+        let ts = backend.current_timestamp().await.unwrap();
+        let mut snapshot = backend.snapshot(ts);
+        assert!(
+            snapshot
+                .scan(key("c")..key("a"), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            snapshot
+                .scan_reverse(key("c")..key("a"), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
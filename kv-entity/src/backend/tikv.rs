@@ -0,0 +1,160 @@
+use std::ops::Range;
+
+use async_trait::async_trait;
+use tikv_client::{Key, KvPair, Timestamp, TransactionClient, Value, proto::kvrpcpb};
+
+use crate::{
+    Error,
+    backend::{Snapshot, StorageBackend, Transaction},
+};
+
+/// 基于 TiKV 事务 API 的存储后端
+#[derive(Clone)]
+pub struct TikvBackend {
+    client: TransactionClient,
+}
+
+impl TikvBackend {
+    pub async fn new(pd_endpoints: Vec<String>) -> Result<Self, Error> {
+        let client = TransactionClient::new(pd_endpoints)
+            .await
+            .map_err(Error::from)?;
+        Ok(Self { client })
+    }
+
+    pub fn from_client(client: TransactionClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for TikvBackend {
+    async fn begin_optimistic(&self) -> Result<Box<dyn Transaction>, Error> {
+        let txn = self.client.begin_optimistic().await.map_err(Error::from)?;
+        Ok(Box::new(TikvTransaction(txn)))
+    }
+
+    async fn begin_pessimistic(&self) -> Result<Box<dyn Transaction>, Error> {
+        let txn = self.client.begin_pessimistic().await.map_err(Error::from)?;
+        Ok(Box::new(TikvTransaction(txn)))
+    }
+
+    async fn current_timestamp(&self) -> Result<Timestamp, Error> {
+        self.client.current_timestamp().await.map_err(Error::from)
+    }
+
+    fn snapshot(&self, timestamp: Timestamp) -> Box<dyn Snapshot> {
+        Box::new(TikvSnapshot(self.client.snapshot(
+            timestamp,
+            tikv_client::TransactionOptions::new_optimistic(),
+        )))
+    }
+}
+
+struct TikvSnapshot(tikv_client::Snapshot);
+
+#[async_trait]
+impl Snapshot for TikvSnapshot {
+    async fn get(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        self.0.get(key).await.map_err(Error::from)
+    }
+
+    async fn batch_get(&mut self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error> {
+        Ok(self.0.batch_get(keys).await.map_err(Error::from)?.collect())
+    }
+
+    async fn scan(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        Ok(self
+            .0
+            .scan(range, limit)
+            .await
+            .map_err(Error::from)?
+            .collect())
+    }
+
+    async fn scan_keys(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<Key>, Error> {
+        Ok(self
+            .0
+            .scan_keys(range, limit)
+            .await
+            .map_err(Error::from)?
+            .collect())
+    }
+
+    async fn scan_reverse(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        Ok(self
+            .0
+            .scan_reverse(range, limit)
+            .await
+            .map_err(Error::from)?
+            .collect())
+    }
+}
+
+struct TikvTransaction(tikv_client::Transaction);
+
+#[async_trait]
+impl Snapshot for TikvTransaction {
+    async fn get(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        self.0.get(key).await.map_err(Error::from)
+    }
+
+    async fn batch_get(&mut self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error> {
+        Ok(self.0.batch_get(keys).await.map_err(Error::from)?.collect())
+    }
+
+    async fn scan(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        Ok(self
+            .0
+            .scan(range, limit)
+            .await
+            .map_err(Error::from)?
+            .collect())
+    }
+
+    async fn scan_keys(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<Key>, Error> {
+        Ok(self
+            .0
+            .scan_keys(range, limit)
+            .await
+            .map_err(Error::from)?
+            .collect())
+    }
+
+    async fn scan_reverse(&mut self, range: Range<Key>, limit: u32) -> Result<Vec<KvPair>, Error> {
+        Ok(self
+            .0
+            .scan_reverse(range, limit)
+            .await
+            .map_err(Error::from)?
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Transaction for TikvTransaction {
+    async fn get_for_update(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        self.0.get_for_update(key).await.map_err(Error::from)
+    }
+
+    async fn put(&mut self, key: Key, value: Value) -> Result<(), Error> {
+        self.0.put(key, value).await.map_err(Error::from)
+    }
+
+    async fn delete(&mut self, key: Key) -> Result<(), Error> {
+        self.0.delete(key).await.map_err(Error::from)
+    }
+
+    async fn batch_mutate(&mut self, mutations: Vec<kvrpcpb::Mutation>) -> Result<(), Error> {
+        self.0.batch_mutate(mutations).await.map_err(Error::from)
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        self.0.commit().await.map_err(Error::from)?;
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        self.0.rollback().await.map_err(Error::from)
+    }
+}
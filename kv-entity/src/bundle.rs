@@ -1,13 +1,40 @@
-use crate::{KvComponent, entity_handler::EntityHandler, error::Error, meta::EntityMetadata};
+use crate::{
+    KvComponent, TypePath, backend::Transaction, entity_handler::EntityHandler, error::Error,
+    meta::EntityMetadata,
+};
 use prost::Message;
-use tikv_client::proto::kvrpcpb;
+use tikv_client::{Value, proto::kvrpcpb};
+
+/// 写入组件时对实体上已有组件的要求
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttachMode {
+    /// 存在则覆盖，不存在则新增
+    Upsert,
+    /// 任意组件已存在时返回 [`Error::AlreadyExists`]
+    Insert,
+    /// 任意组件不存在时返回 [`Error::NotFound`]
+    Replace,
+}
+
+impl AttachMode {
+    /// 根据元数据中记录的组件检查是否允许写入
+    fn check(self, metadata: &EntityMetadata, type_path: TypePath) -> Result<(), Error> {
+        let exists = metadata.component_archetypes.contains_key(type_path.0);
+        match self {
+            AttachMode::Insert if exists => Err(Error::AlreadyExists),
+            AttachMode::Replace if !exists => Err(Error::NotFound),
+            _ => Ok(()),
+        }
+    }
+}
 
 pub trait ComponentBundle: Sized {
     fn attach_to(
         self,
         entity: &EntityHandler,
-        txn: &mut tikv_client::Transaction,
+        txn: &mut dyn Transaction,
         mutations: &mut Vec<kvrpcpb::Mutation>,
+        mode: AttachMode,
     ) -> impl std::future::Future<Output = Result<(), Error>> + Send;
 
     fn clone(&self) -> Self;
@@ -20,15 +47,17 @@ where
     async fn attach_to(
         self,
         entity: &EntityHandler,
-        txn: &mut tikv_client::Transaction,
+        txn: &mut dyn Transaction,
         mutations: &mut Vec<kvrpcpb::Mutation>,
+        mode: AttachMode,
     ) -> Result<(), Error> {
         let mut metadata = entity
             .get_metadata(txn)
             .await?
             .unwrap_or(EntityMetadata::default());
+        mode.check(&metadata, T::type_path())?;
         entity
-            .attach_component_in_txn(mutations, &mut metadata, self)
+            .attach_component_in_txn(txn, mutations, &mut metadata, self)
             .await?;
 
         entity.update_metadata(txn, metadata).await?;
@@ -46,16 +75,25 @@ macro_rules! impl_component_bundle_for_tuple {
         where
             $($T: KvComponent + Message + Default + Clone, )+
         {
-            async fn attach_to(self, entity: &EntityHandler, txn: &mut tikv_client::Transaction, mutations: &mut Vec<kvrpcpb::Mutation>) -> Result<(), Error> {
+            async fn attach_to(
+                self,
+                entity: &EntityHandler,
+                txn: &mut dyn Transaction,
+                mutations: &mut Vec<kvrpcpb::Mutation>,
+                mode: AttachMode,
+            ) -> Result<(), Error> {
                 let mut metadata = entity
                     .get_metadata(txn)
                     .await?
                     .unwrap_or(EntityMetadata::default());
+                $(
+                    mode.check(&metadata, $T::type_path())?;
+                )+
 
                 #[allow(non_snake_case)]
                 let ($($T,)+) = self;
                 $(
-                    entity.attach_component_in_txn(mutations, &mut metadata, $T).await?;
+                    entity.attach_component_in_txn(txn, mutations, &mut metadata, $T).await?;
                 )+
 
                 entity.update_metadata(txn, metadata).await?;
@@ -80,3 +118,52 @@ impl_component_bundle_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9);
 impl_component_bundle_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10);
 impl_component_bundle_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11);
 impl_component_bundle_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
+
+/// 可以通过一次 `batch_get` 同时读取的一组组件，读取结果为对应的 `Option` 元组
+pub trait ComponentSet {
+    type Output;
+
+    fn type_paths() -> Vec<TypePath>;
+
+    /// `values` 按组件顺序排列，None 表示实体没有该组件
+    fn decode(values: Vec<Option<Value>>) -> Result<Self::Output, Error>;
+}
+
+macro_rules! impl_component_set_for_tuple {
+    ($($T:ident),+) => {
+        impl<$($T),+> ComponentSet for ($($T,)+)
+        where
+            $($T: KvComponent + Message + Default, )+
+        {
+            type Output = ($(Option<$T>,)+);
+
+            fn type_paths() -> Vec<TypePath> {
+                vec![$($T::type_path()),+]
+            }
+
+            fn decode(values: Vec<Option<Value>>) -> Result<Self::Output, Error> {
+                let mut values = values.into_iter();
+                Ok(($(
+                    values
+                        .next()
+                        .flatten()
+                        .map(|data| $T::decode(data.as_slice()).map_err(Error::DeserializationError))
+                        .transpose()?,
+                )+))
+            }
+        }
+    };
+}
+
+impl_component_set_for_tuple!(T1);
+impl_component_set_for_tuple!(T1, T2);
+impl_component_set_for_tuple!(T1, T2, T3);
+impl_component_set_for_tuple!(T1, T2, T3, T4);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6, T7);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11);
+impl_component_set_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
@@ -0,0 +1,219 @@
+use std::{collections::HashSet, marker::PhantomData};
+
+use async_trait::async_trait;
+use prost::Message;
+
+use crate::{
+    DB, Error, KvComponent, KvRelation, RelationDirection, TypePath,
+    backend::Snapshot,
+    component_data_path,
+    db::EntityID,
+    entity_handler::{EntityHandler, EntityListHandler},
+    filter::Filter,
+    utils::key_to_string,
+};
+
+/// 链式查询的起点
+#[async_trait]
+pub(crate) trait EntitySource: Send + Sync {
+    async fn entity_ids(&self, snapshot: &mut dyn Snapshot) -> Result<Vec<EntityID>, Error>;
+}
+
+#[async_trait]
+impl<T> EntitySource for Filter<T>
+where
+    T: KvComponent + Message + Default + Send + Sync,
+{
+    async fn entity_ids(&self, snapshot: &mut dyn Snapshot) -> Result<Vec<EntityID>, Error> {
+        self.query_entity_id_vec(snapshot).await
+    }
+}
+
+#[async_trait]
+impl EntitySource for Vec<EntityID> {
+    async fn entity_ids(&self, _snapshot: &mut dyn Snapshot) -> Result<Vec<EntityID>, Error> {
+        Ok(self.clone())
+    }
+}
+
+enum Hop {
+    /// 沿关系走到相邻的实体
+    Follow {
+        type_path: TypePath,
+        direction: RelationDirection,
+    },
+    /// 只保留拥有该组件的实体
+    With { type_path: TypePath },
+}
+
+/// 惰性的多层链式查询，通过 [`Filter::follow`]、[`Filter::with`] 或 [`EntityHandler::follow`] 构建
+///
+/// 在调用 `collect` 等方法之前不会读取数据，所有的步骤在同一个快照上执行，
+/// 每一步的组件读取合并为一次 `batch_get`。`T` 为最后一次 `with` 的组件类型
+pub struct Chain<T = ()> {
+    client: DB,
+    source: Box<dyn EntitySource>,
+    hops: Vec<Hop>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Chain<T> {
+    pub(crate) fn new(client: DB, source: Box<dyn EntitySource>) -> Self {
+        Self {
+            client,
+            source,
+            hops: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn hop<U>(mut self, hop: Hop) -> Chain<U> {
+        self.hops.push(hop);
+        Chain {
+            client: self.client,
+            source: self.source,
+            hops: self.hops,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 沿 `R` 关系走到相邻的实体，方向与 [`EntityHandler::edges`] 相同，相同的实体只保留一次
+    pub fn follow<R: KvRelation>(self, direction: RelationDirection) -> Chain<()> {
+        self.hop(Hop::Follow {
+            type_path: R::type_path(),
+            direction,
+        })
+    }
+
+    /// 只保留拥有组件 `C` 的实体
+    pub fn with<C: KvComponent + Message + Default>(self) -> Chain<C> {
+        self.hop(Hop::With {
+            type_path: C::type_path(),
+        })
+    }
+
+    /// 执行所有步骤，返回最终的实体和最后一次 `with` 读取到的组件数据
+    async fn run(&self) -> Result<Vec<(EntityID, Option<Vec<u8>>)>, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        let mut entities = self
+            .source
+            .entity_ids(&mut *snapshot)
+            .await?
+            .into_iter()
+            .map(|entity_id| (entity_id, None))
+            .collect::<Vec<_>>();
+
+        for hop in self.hops.iter() {
+            entities = match hop {
+                Hop::Follow {
+                    type_path,
+                    direction,
+                } => {
+                    let mut visited = HashSet::new();
+                    let mut next = Vec::new();
+                    for (entity_id, _) in entities {
+                        let edges = self
+                            .client
+                            .entity(entity_id)
+                            .edges_entity_in_txn(*type_path, *direction, &mut *snapshot)
+                            .await?;
+                        for (entity_id, _) in edges {
+                            if visited.insert(entity_id.clone()) {
+                                next.push((entity_id, None));
+                            }
+                        }
+                    }
+                    next
+                }
+                Hop::With { type_path } => {
+                    let mut values = snapshot
+                        .batch_get(
+                            entities
+                                .iter()
+                                .map(|(entity_id, _)| {
+                                    component_data_path(*type_path, entity_id).into()
+                                })
+                                .collect(),
+                        )
+                        .await?
+                        .into_iter()
+                        .map(|kv| Ok((key_to_string(kv.key())?, kv.into_value())))
+                        .collect::<Result<std::collections::HashMap<_, _>, Error>>()?;
+                    entities
+                        .into_iter()
+                        .filter_map(|(entity_id, _)| {
+                            let value =
+                                values.remove(&component_data_path(*type_path, &entity_id))?;
+                            Some((entity_id, Some(value)))
+                        })
+                        .collect()
+                }
+            };
+        }
+        Ok(entities)
+    }
+
+    pub async fn entity_ids(&self) -> Result<Vec<EntityID>, Error> {
+        Ok(self
+            .run()
+            .await?
+            .into_iter()
+            .map(|(entity_id, _)| entity_id)
+            .collect())
+    }
+
+    pub async fn count(&self) -> Result<u64, Error> {
+        Ok(self.run().await?.len() as u64)
+    }
+
+    pub async fn list(&self) -> Result<EntityListHandler, Error> {
+        Ok(EntityListHandler::new(
+            self.entity_ids().await?,
+            self.client.clone(),
+        ))
+    }
+}
+
+impl<T> Chain<T>
+where
+    T: KvComponent + Message + Default,
+{
+    /// 返回最终的实体和组件 `T`
+    pub async fn collect(&self) -> Result<Vec<(EntityID, T)>, Error> {
+        self.run()
+            .await?
+            .into_iter()
+            .filter_map(|(entity_id, value)| {
+                let value = value?;
+                Some(
+                    T::decode(value.as_slice())
+                        .map(|value| (entity_id, value))
+                        .map_err(Error::DeserializationError),
+                )
+            })
+            .collect()
+    }
+}
+
+impl<T> Filter<T>
+where
+    T: KvComponent + Message + Default + Send + Sync + 'static,
+{
+    /// 从检索到的实体出发，沿 `R` 关系走到相邻的实体
+    pub fn follow<R: KvRelation>(self, direction: RelationDirection) -> Chain<()> {
+        Chain::<()>::new(self.client(), Box::new(self)).follow::<R>(direction)
+    }
+
+    /// 读取检索到的实体上的组件 `C`
+    pub fn with<C: KvComponent + Message + Default>(self) -> Chain<C> {
+        Chain::<()>::new(self.client(), Box::new(self)).with::<C>()
+    }
+}
+
+impl EntityHandler {
+    /// 从当前实体出发，沿 `R` 关系走到相邻的实体
+    pub fn follow<R: KvRelation>(&self, direction: RelationDirection) -> Chain<()> {
+        Chain::<()>::new(self.client.clone(), Box::new(vec![self.entity_id.clone()]))
+            .follow::<R>(direction)
+    }
+}
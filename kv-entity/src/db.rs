@@ -1,39 +1,234 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use async_stream::try_stream;
 use futures::Stream;
-use tikv_client::{Key, TransactionClient};
+use tikv_client::{Key, Timestamp, TimestampExt};
 
 use crate::{
-    KvComponent, component_data_path,
+    KvComponent, KvRelation,
+    archetype::{Archetype, ArchetypeQuery},
+    backend::{
+        MemoryBackend, PHYSICAL_SHIFT_BITS, SharedTransaction, Snapshot, StorageBackend,
+        TikvBackend,
+    },
+    component_data_path,
     entity_handler::{EntityHandler, EntityListHandler},
     error::Error,
-    next_key,
+    key_after,
+    retry::RetryPolicy,
+    transaction_handler::TransactionHandler,
     utils::{component_increment_id_path, key_to_string},
+    view::ReadView,
 };
 
 #[derive(Clone)]
 pub struct DB {
-    pub(crate) client: TransactionClient,
+    pub(crate) backend: Arc<dyn StorageBackend>,
+    pub(crate) retry_policy: RetryPolicy,
+    /// 固定的读取时间戳，为 None 时每次读取使用当前时间戳
+    pub(crate) read_timestamp: Option<Timestamp>,
+    /// 为 true 时 `link` 要求两端的实体都存在
+    pub(crate) strict_links: bool,
 }
 
 impl DB {
     pub async fn new(pd_endpoints: Vec<String>) -> Result<Self, Error> {
-        let client = TransactionClient::new(pd_endpoints)
-            .await
-            .map_err(Error::TikvError)?;
-        Ok(Self { client })
+        Ok(Self::with_backend(TikvBackend::new(pd_endpoints).await?))
+    }
+
+    /// 使用内存存储，无需 TiKV 集群即可运行，适合测试
+    pub fn new_in_memory() -> Self {
+        Self::with_backend(MemoryBackend::new())
+    }
+
+    pub fn with_backend(backend: impl StorageBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            retry_policy: RetryPolicy::default(),
+            read_timestamp: None,
+            strict_links: false,
+        }
+    }
+
+    /// 设置事务冲突时的重试策略
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// 开启后 `link` 在事务中检查两端的实体是否存在，不存在时返回 [`Error::NotFound`]，
+    /// 并锁定两端实体的元数据，使并发删除其中任一实体的事务发生冲突
+    pub fn with_strict_links(mut self, strict_links: bool) -> Self {
+        self.strict_links = strict_links;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub(crate) async fn snapshot(&self) -> Result<Box<dyn Snapshot>, Error> {
+        let timestamp = match &self.read_timestamp {
+            Some(timestamp) => timestamp.clone(),
+            None => self.backend.current_timestamp().await?,
+        };
+        Ok(self.backend.snapshot(timestamp))
+    }
+
+    /// 固定在当前时间戳上的只读会话，多次读取看到同一个一致的状态
+    pub async fn read_session(&self) -> Result<ReadView, Error> {
+        let timestamp = self.backend.current_timestamp().await?;
+        Ok(self.at(timestamp))
+    }
+
+    /// 在 `timestamp` 时刻的只读视图，需要在 TiKV 的 GC 保留时间之内
+    pub fn at(&self, timestamp: Timestamp) -> ReadView {
+        ReadView::new(self.clone(), timestamp)
+    }
+
+    /// 同 [`DB::at`]，按物理时间定位，包含该毫秒内提交的全部数据
+    pub fn at_time(&self, time: SystemTime) -> ReadView {
+        let physical = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let logical = (1 << PHYSICAL_SHIFT_BITS) - 1;
+        self.at(Timestamp::from_version(
+            (physical << PHYSICAL_SHIFT_BITS) | logical,
+        ))
+    }
+
+    /// 在同一个乐观事务中执行 `f` 中的所有操作，`f` 返回 `Ok` 时提交，返回 `Err` 时回滚
+    ///
+    /// 发生冲突时会按重试策略重新执行整个 `f`，因此 `f` 中不应包含事务之外的副作用
+    pub async fn transaction<R, F, Fut>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnMut(TransactionHandler) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        let (value, _) = self.transaction_with_attempts(f).await?;
+        Ok(value)
+    }
+
+    /// 同 [`DB::transaction`]，同时返回提交成功时一共尝试的次数
+    pub async fn transaction_with_attempts<R, F, Fut>(&self, mut f: F) -> Result<(R, u32), Error>
+    where
+        F: FnMut(TransactionHandler) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        self.run_in_txn(false, |txn| {
+            f(TransactionHandler {
+                txn,
+                client: self.clone(),
+            })
+        })
+        .await
+    }
+
+    /// 在乐观事务中执行 `f` 并提交，冲突时按重试策略重新执行整个 `f`
+    pub(crate) async fn run_optimistic<R, F, Fut>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnMut(SharedTransaction) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        let (value, _) = self.run_in_txn(false, f).await?;
+        Ok(value)
+    }
+
+    /// 同 [`DB::run_optimistic`]，同时返回提交成功时一共尝试的次数
+    pub(crate) async fn run_optimistic_with_attempts<R, F, Fut>(
+        &self,
+        f: F,
+    ) -> Result<(R, u32), Error>
+    where
+        F: FnMut(SharedTransaction) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        self.run_in_txn(false, f).await
+    }
+
+    /// 同 [`DB::run_optimistic`]，但使用悲观事务
+    pub(crate) async fn run_pessimistic<R, F, Fut>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnMut(SharedTransaction) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        let (value, _) = self.run_in_txn(true, f).await?;
+        Ok(value)
+    }
+
+    /// 返回结果和提交成功时一共尝试的次数
+    async fn run_in_txn<R, F, Fut>(&self, pessimistic: bool, mut f: F) -> Result<(R, u32), Error>
+    where
+        F: FnMut(SharedTransaction) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let txn = if pessimistic {
+                self.backend.begin_pessimistic().await?
+            } else {
+                self.backend.begin_optimistic().await?
+            };
+            let txn: SharedTransaction = Arc::new(futures::lock::Mutex::new(txn));
+            let result = f(txn.clone()).await;
+            let mut txn = txn.lock().await;
+            let result = match result {
+                Ok(value) => txn.commit().await.map(|_| value),
+                Err(error) => {
+                    // 回滚失败时保留原始错误，仍按原始错误决定是否重试
+                    if let Err(rollback_error) = txn.rollback().await {
+                        log::warn!("transaction rollback failed: {}", rollback_error);
+                    }
+                    Err(error)
+                }
+            };
+            drop(txn);
+            let error = match result {
+                Ok(value) => {
+                    if attempt > 1 {
+                        log::debug!("transaction committed after {} attempts", attempt);
+                    }
+                    return Ok((value, attempt));
+                }
+                Err(error) => error,
+            };
+            if self.retry_policy.should_retry(&error, attempt) {
+                let backoff = self.retry_policy.backoff_for(attempt);
+                log::debug!(
+                    "transaction attempt {} failed: {}, retry after {:?}",
+                    attempt,
+                    error,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                continue;
+            }
+            if attempt > 1 && (self.retry_policy.retryable)(&error) {
+                return Err(Error::RetryExhausted {
+                    attempts: attempt,
+                    source: Box::new(error),
+                });
+            }
+            return Err(error);
+        }
     }
 
     pub fn entity(&self, entity_id: impl Into<EntityID>) -> EntityHandler {
         EntityHandler {
             entity_id: entity_id.into(),
-            client: self.client.clone(),
+            client: self.clone(),
         }
     }
 
     pub async fn resource(&self) -> EntityHandler {
         EntityHandler {
             entity_id: EntityID::resource(),
-            client: self.client.clone(),
+            client: self.clone(),
         }
     }
 
@@ -41,30 +236,39 @@ impl DB {
         T::query(self.clone())
     }
 
+    pub fn query_relation<T: KvRelation + prost::Message + Default>(&self) -> T::Query {
+        T::query(self.clone())
+    }
+
+    /// 原型查询，例如 `db.query_archetype::<(With<A>, With<B>, Without<C>)>()`
+    ///
+    /// 至少需要一个 [`crate::With`]，否则查询时返回 [`Error::InvalidArchetype`]
+    pub fn query_archetype<A>(&self) -> ArchetypeQuery<A>
+    where
+        A: Archetype + 'static,
+        A::Item: Send,
+    {
+        ArchetypeQuery::new(self.clone())
+    }
+
+    #[allow(clippy::type_complexity)]
     pub fn get<T: KvComponent + prost::Message + Default + 'static>(
         &self,
     ) -> std::pin::Pin<Box<dyn Stream<Item = Result<(EntityID, T), Error>> + '_>> {
         const PAGE_SIZE: usize = 128;
         Box::pin(try_stream! {
-            let mut snapshot = self.client.snapshot(
-                self.client
-                    .current_timestamp()
-                    .await
-                    .map_err(Error::TikvError)?,
-                tikv_client::TransactionOptions::new_optimistic(),
-            );
+            let mut snapshot = self.snapshot().await?;
 
             let mut start_key: Key = component_data_path(T::type_path(), &EntityID::Empty).into();
             let end_key: Key = component_data_path(T::type_path(), &EntityID::Max).into();
             loop {
                 let kvs = snapshot
                     .scan(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                    .await
-                    .map_err(Error::TikvError)?.collect::<Vec<_>>();
+                    .await?;
                 if kvs.is_empty() {
                     break;
                 }
-                start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.key().clone());
+                start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.key().clone());
                 let len = kvs.len();
                 for kv in kvs {
                     let key = key_to_string(kv.key())?
@@ -89,13 +293,7 @@ impl DB {
         &self,
     ) -> Result<EntityListHandler, Error> {
         const PAGE_SIZE: usize = 128;
-        let mut snapshot = self.client.snapshot(
-            self.client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
+        let mut snapshot = self.snapshot().await?;
 
         let mut start_key: Key = component_data_path(T::type_path(), &EntityID::Empty).into();
         let end_key: Key = component_data_path(T::type_path(), &EntityID::Max).into();
@@ -104,13 +302,11 @@ impl DB {
         loop {
             let kvs = snapshot
                 .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                .await
-                .map_err(Error::TikvError)?
-                .collect::<Vec<_>>();
+                .await?;
             if kvs.is_empty() {
                 break;
             }
-            start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.clone());
+            start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.clone());
             let len = kvs.len();
             for kv in kvs {
                 let key = key_to_string(&kv)?
@@ -129,33 +325,24 @@ impl DB {
         }
         Ok(EntityListHandler {
             entity_ids,
-            client: self.client.clone(),
+            client: self.clone(),
         })
     }
 
     pub async fn keys(&self) -> Result<(), Error> {
         const PAGE_SIZE: usize = 128;
-        let mut tnx = self
-            .client
-            .begin_with_options(
-                tikv_client::TransactionOptions::new_optimistic()
-                    .drop_check(tikv_client::CheckLevel::Warn),
-            )
-            .await
-            .map_err(Error::TikvError)?;
-        let mut start_key: Key = format!("").into();
-        let end_key: Key = format!("~").into();
+        let mut snapshot = self.snapshot().await?;
+        let mut start_key: Key = String::new().into();
+        let end_key: Key = "~".to_string().into();
 
         loop {
-            let kvs = tnx
+            let kvs = snapshot
                 .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                .await
-                .map_err(Error::TikvError)?
-                .collect::<Vec<_>>();
+                .await?;
             if kvs.is_empty() {
                 break;
             }
-            start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.clone());
+            start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.clone());
             let len = kvs.len();
             for kv in kvs {
                 let key = key_to_string(&kv)?;
@@ -165,7 +352,6 @@ impl DB {
                 break;
             }
         }
-        tnx.commit().await.map_err(Error::TikvError)?;
         Ok(())
     }
 
@@ -173,33 +359,28 @@ impl DB {
         &self,
         value: T,
     ) -> Result<EntityHandler, Error> {
-        let data_key = component_increment_id_path(T::type_path());
-
-        let mut txn = self
-            .client
-            .begin_pessimistic()
-            .await
-            .map_err(Error::TikvError)?;
-        let id = match txn
-            .get_for_update(data_key.clone())
-            .await
-            .map_err(Error::TikvError)?
-        {
-            Some(id) => {
-                let id = String::from_utf8(id.as_slice().to_vec()).map_err(Error::InvalidUtf8)?;
-                id.parse::<u64>().map_err(Error::InvalidU64)?
-            }
-            None => 0,
-        };
+        let data_key = &component_increment_id_path(T::type_path());
 
-        txn.put(data_key, format!("{:032x}", id + 1))
-            .await
-            .map_err(Error::TikvError)?;
-        txn.commit().await.map_err(Error::TikvError)?;
+        let id = self
+            .run_pessimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                let id = match txn.get_for_update(data_key.clone().into()).await? {
+                    Some(id) => {
+                        let id = String::from_utf8(id.as_slice().to_vec())
+                            .map_err(Error::InvalidUtf8)?;
+                        id.parse::<u64>().map_err(Error::InvalidU64)?
+                    }
+                    None => 0,
+                };
+                txn.put(data_key.clone().into(), format!("{:032x}", id + 1).into())
+                    .await?;
+                Ok(id)
+            })
+            .await?;
 
         let entity_handler = EntityHandler {
             entity_id: EntityID::new(format!("{:032x}", id)),
-            client: self.client.clone(),
+            client: self.clone(),
         };
 
         entity_handler.attach(value).await?;
@@ -207,7 +388,7 @@ impl DB {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum EntityID {
     Resource,
     Entity(String),
@@ -227,8 +408,11 @@ impl EntityID {
         Self::Resource
     }
 
+    /// 从 key 中的实体片段还原 [`EntityID`]，与 `Into<String>` 互逆
     pub(crate) fn new_raw(entity_id: String) -> Self {
-        if let Some(entity_id) = entity_id.strip_prefix("e-") {
+        if entity_id == RESOURCE_ID {
+            Self::Resource
+        } else if let Some(entity_id) = entity_id.strip_prefix("e-") {
             Self::Entity(entity_id.to_string())
         } else {
             Self::Entity(entity_id)
@@ -238,9 +422,9 @@ impl EntityID {
 
 const RESOURCE_ID: &str = "resource";
 
-impl Into<String> for EntityID {
-    fn into(self) -> String {
-        match self {
+impl From<EntityID> for String {
+    fn from(entity_id: EntityID) -> Self {
+        match entity_id {
             EntityID::Resource => RESOURCE_ID.to_string(),
             EntityID::Entity(entity_id) => format!("e-{}", entity_id),
             EntityID::Empty => "".to_string(),
@@ -249,21 +433,21 @@ impl Into<String> for EntityID {
     }
 }
 
-impl Into<EntityID> for &str {
-    fn into(self) -> EntityID {
-        if self.find("/").is_some() {
+impl From<&str> for EntityID {
+    fn from(entity_id: &str) -> Self {
+        if entity_id.find("/").is_some() {
             panic!("entity_id must not contain '/'");
         }
-        EntityID::Entity(self.to_string())
+        EntityID::Entity(entity_id.to_string())
     }
 }
 
-impl Into<EntityID> for String {
-    fn into(self) -> EntityID {
-        if self.find("/").is_some() {
+impl From<String> for EntityID {
+    fn from(entity_id: String) -> Self {
+        if entity_id.find("/").is_some() {
             panic!("entity_id must not contain '/'");
         }
-        EntityID::Entity(self)
+        EntityID::Entity(entity_id)
     }
 }
 
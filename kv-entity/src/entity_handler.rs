@@ -1,23 +1,31 @@
+use std::collections::{HashMap, HashSet};
+
 use async_stream::try_stream;
 use futures::Stream;
 use prost::Message;
-use tikv_client::{Key, TransactionClient, proto::kvrpcpb};
+use tikv_client::{Key, proto::kvrpcpb};
 
 use crate::{
-    Error, KvComponent, KvRelation, RelationDirection, TypePath,
-    bundle::ComponentBundle,
+    DB, Error, KvComponent, KvRelation, RelationDirection, TypePath,
+    backend::{Snapshot, Transaction},
+    bundle::{AttachMode, ComponentBundle, ComponentSet},
     component_data_path, component_index_path,
     db::EntityID,
-    entity_metadata_path,
+    entity_metadata_path, key_after,
     meta::{ComponentArchetype, EntityMetadata},
-    next_key, relation_data_path, relation_edge_no_type_path, relation_edge_path,
-    utils::{intern_string, key_to_string},
+    relation_data_path, relation_edge_no_type_path, relation_edge_path,
+    utils::{
+        Cardinality, DeletePolicy, component_unique_path, entity_revision_path, intern_string,
+        key_to_string, relation_cardinality, relation_degree_path, relation_delete_policy,
+        relation_index_path, relation_layout, relation_meta, relation_single_path,
+        relation_symmetric,
+    },
 };
 
 #[derive(Clone)]
 pub struct EntityHandler {
     pub(crate) entity_id: EntityID,
-    pub(crate) client: TransactionClient,
+    pub(crate) client: DB,
 }
 
 impl EntityHandler {
@@ -26,117 +34,159 @@ impl EntityHandler {
     }
 
     pub async fn get<T: KvComponent + prost::Message + Default>(&self) -> Result<Option<T>, Error> {
-        let mut snapshot = self.client.snapshot(
-            self.client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
-
-        let Some(data) = snapshot
-            .get(component_data_path(T::type_path(), &self.entity_id))
-            .await
-            .map_err(Error::TikvError)?
-        else {
-            return Ok(None);
-        };
-
-        let message = T::decode(data.as_slice()).map_err(Error::DeserializationError)?;
+        let mut snapshot = self.client.snapshot().await?;
+        self.get_in_txn(&mut *snapshot).await
+    }
 
-        Ok(Some(message))
+    /// 在同一个快照中通过一次 `batch_get` 读取多个组件，例如 `get_many::<(A, B, C)>()`
+    pub async fn get_many<S: ComponentSet>(&self) -> Result<S::Output, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        self.get_many_in_txn::<S>(&mut *snapshot).await
     }
 
     pub async fn attach(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
-        let mut txn = self
-            .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
-        let mut mutations = Vec::new();
-        bundle.attach_to(self, &mut txn, &mut mutations).await?;
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
-        txn.commit().await.map_err(Error::TikvError)?;
-        Ok(self.clone())
+        let (handler, _) = self.attach_with_attempts(bundle).await?;
+        Ok(handler)
     }
 
-    pub async fn detach<T: KvComponent + prost::Message + Default>(&self) -> Result<Self, Error> {
-        let mut txn: tikv_client::Transaction = self
+    /// 同 [`EntityHandler::attach`]，同时返回提交成功时一共尝试的次数
+    pub async fn attach_with_attempts(
+        &self,
+        bundle: impl ComponentBundle,
+    ) -> Result<(Self, u32), Error> {
+        let bundle = &bundle;
+        let (_, attempts) = self
             .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
+            .run_optimistic_with_attempts(|txn| async move {
+                self.attach_in_txn(&mut **txn.lock().await, bundle.clone(), AttachMode::Upsert)
+                    .await
+            })
+            .await?;
+        Ok((self.clone(), attempts))
+    }
 
-        if !T::indexed_field_names().is_empty() {
-            let Some(mut metadata) = self.get_metadata(&mut txn).await? else {
-                txn.rollback().await.map_err(Error::TikvError)?;
-                return Err(Error::NotFound);
-            };
-            let mut mutations = Vec::new();
-            for key in metadata
-                .component_archetypes
-                .remove(T::type_path().0)
-                .ok_or(Error::NotFound)?
-                .index_keys
-                .values()
-            {
-                mutations.push(kvrpcpb::Mutation {
-                    key: key.clone().into(),
-                    op: kvrpcpb::Op::Del.into(),
-                    ..Default::default()
-                });
-            }
-            txn.batch_mutate(mutations)
-                .await
-                .map_err(Error::TikvError)?;
-            self.update_metadata(&mut txn, metadata).await?;
-        }
+    /// 写入组件，`bundle` 中任意组件已存在时返回 [`Error::AlreadyExists`]
+    pub async fn insert(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
+        let bundle = &bundle;
+        self.client
+            .run_optimistic(|txn| async move {
+                self.attach_in_txn(&mut **txn.lock().await, bundle.clone(), AttachMode::Insert)
+                    .await
+            })
+            .await?;
+        Ok(self.clone())
+    }
+
+    /// 覆盖组件，`bundle` 中任意组件不存在时返回 [`Error::NotFound`]
+    pub async fn replace(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
+        let bundle = &bundle;
+        self.client
+            .run_optimistic(|txn| async move {
+                self.attach_in_txn(&mut **txn.lock().await, bundle.clone(), AttachMode::Replace)
+                    .await
+            })
+            .await?;
+        Ok(self.clone())
+    }
 
-        txn.delete(component_data_path(T::type_path(), &self.entity_id))
+    /// 在同一个事务中读取组件、调用 `f` 修改后写回，返回修改后的值，组件不存在时返回 [`Error::NotFound`]
+    ///
+    /// 发生冲突时会重新读取并再次调用 `f`，因此 `f` 中不应包含副作用
+    pub async fn update<T>(&self, f: impl Fn(&mut T)) -> Result<T, Error>
+    where
+        T: KvComponent + prost::Message + Default + Clone,
+    {
+        let f = &f;
+        self.client
+            .run_optimistic(|txn| async move {
+                self.update_in_txn(&mut **txn.lock().await, || None, f)
+                    .await
+            })
             .await
-            .map_err(Error::TikvError)?;
+    }
 
-        txn.commit().await.map_err(Error::TikvError)?;
+    /// 同 [`EntityHandler::update`]，组件不存在时以 `default` 的返回值为初始值
+    pub async fn upsert_with<T>(
+        &self,
+        default: impl Fn() -> T,
+        f: impl Fn(&mut T),
+    ) -> Result<T, Error>
+    where
+        T: KvComponent + prost::Message + Default + Clone,
+    {
+        let default = &default;
+        let f = &f;
+        self.client
+            .run_optimistic(|txn| async move {
+                self.update_in_txn(&mut **txn.lock().await, || Some(default()), f)
+                    .await
+            })
+            .await
+    }
 
-        Ok(self.clone())
+    pub async fn detach<T: KvComponent + prost::Message + Default>(&self) -> Result<Self, Error> {
+        let (handler, _) = self.detach_with_attempts::<T>().await?;
+        Ok(handler)
     }
 
-    pub async fn delete(&self) -> Result<Self, Error> {
-        let mut txn = self
+    /// 同 [`EntityHandler::detach`]，同时返回提交成功时一共尝试的次数
+    pub async fn detach_with_attempts<T: KvComponent + prost::Message + Default>(
+        &self,
+    ) -> Result<(Self, u32), Error> {
+        let (_, attempts) = self
             .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
+            .run_optimistic_with_attempts(|txn| async move {
+                self.detach_in_txn::<T>(&mut **txn.lock().await).await
+            })
+            .await?;
 
-        let mut mutations = Vec::new();
+        Ok((self.clone(), attempts))
+    }
 
-        self.delete_in_txn(&mut txn, &mut mutations).await?;
+    pub async fn delete(&self) -> Result<Self, Error> {
+        let (handler, _) = self.delete_with_attempts().await?;
+        Ok(handler)
+    }
 
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
-        txn.commit().await.map_err(Error::TikvError)?;
-        Ok(self.clone())
+    /// 同 [`EntityHandler::delete`]，同时返回提交成功时一共尝试的次数
+    pub async fn delete_with_attempts(&self) -> Result<(Self, u32), Error> {
+        let (_, attempts) = self
+            .client
+            .run_optimistic_with_attempts(|txn| async move {
+                let mut txn = txn.lock().await;
+                let mut mutations = Vec::new();
+                self.delete_in_txn(&mut **txn, &mut mutations).await?;
+                txn.batch_mutate(mutations).await
+            })
+            .await?;
+        Ok((self.clone(), attempts))
     }
 
     pub async fn metadata(&self) -> Result<EntityMetadata, Error> {
-        let mut snapshot = self.client.snapshot(
-            self.client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
+        let mut snapshot = self.client.snapshot().await?;
         let Some(metadata) = snapshot
-            .get(entity_metadata_path(&self.entity_id))
-            .await
-            .map_err(Error::TikvError)?
+            .get(entity_metadata_path(&self.entity_id).into())
+            .await?
         else {
             return Err(Error::NotFound);
         };
-        Ok(EntityMetadata::decode(metadata.as_slice()).map_err(Error::DeserializationError)?)
+        EntityMetadata::decode(metadata.as_slice()).map_err(Error::DeserializationError)
+    }
+
+    /// 实体的版本号，每次 attach、detach、link、unlink 时递增，实体不存在时为 0
+    ///
+    /// 删除后重新创建的实体从删除前的版本号继续递增，旧的版本号不会再次出现
+    pub async fn revision(&self) -> Result<u64, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        self.revision_in_txn(&mut *snapshot).await
+    }
+
+    /// 仅当实体的版本号等于 `revision` 时才执行后续的写入，否则返回 [`Error::RevisionMismatch`]
+    pub fn if_revision(&self, revision: u64) -> ConditionalEntityHandler {
+        ConditionalEntityHandler {
+            entity: self.clone(),
+            revision,
+        }
     }
 
     pub async fn link<T: KvRelation + prost::Message + Default>(
@@ -144,51 +194,43 @@ impl EntityHandler {
         entity_id: impl Into<EntityID>,
         value: T,
     ) -> Result<Self, Error> {
-        let entity_id = entity_id.into();
-        let mutations = vec![
-            kvrpcpb::Mutation {
-                key: relation_edge_path(
-                    T::type_path(),
-                    &self.entity_id,
-                    &entity_id,
-                    RelationDirection::In,
-                )
-                .into(),
-                op: kvrpcpb::Op::Put.into(),
-                value: [].into(),
-                ..Default::default()
-            },
-            kvrpcpb::Mutation {
-                key: relation_edge_path(
-                    T::type_path(),
-                    &entity_id,
-                    &self.entity_id,
-                    RelationDirection::Out,
-                )
-                .into(),
-                op: kvrpcpb::Op::Put.into(),
-                value: [].into(),
-                ..Default::default()
-            },
-            kvrpcpb::Mutation {
-                key: relation_data_path(T::type_path(), &self.entity_id, &entity_id).into(),
-                op: kvrpcpb::Op::Put.into(),
-                value: value.encode_to_vec(),
-                ..Default::default()
-            },
-        ];
+        let (handler, _) = self.link_with_attempts(entity_id, value).await?;
+        Ok(handler)
+    }
 
-        let mut txn = self
+    /// 同 [`EntityHandler::link`]，同时返回提交成功时一共尝试的次数
+    pub async fn link_with_attempts<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        value: T,
+    ) -> Result<(Self, u32), Error> {
+        let entity_id = &entity_id.into();
+        let value = &value;
+        let (_, attempts) = self
             .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
-
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
+            .run_optimistic_with_attempts(|txn| async move {
+                self.link_in_txn(&mut **txn.lock().await, entity_id, value, false)
+                    .await
+            })
+            .await?;
+        Ok((self.clone(), attempts))
+    }
 
-        txn.commit().await.map_err(Error::TikvError)?;
+    /// 与 [`EntityHandler::link`] 相同，但关系受基数约束时不会返回 [`Error::CardinalityViolation`]，
+    /// 而是在同一个事务中解除已有的关系
+    pub async fn link_replace<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        value: T,
+    ) -> Result<Self, Error> {
+        let entity_id = &entity_id.into();
+        let value = &value;
+        self.client
+            .run_optimistic(|txn| async move {
+                self.link_in_txn(&mut **txn.lock().await, entity_id, value, true)
+                    .await
+            })
+            .await?;
         Ok(self.clone())
     }
 
@@ -196,51 +238,64 @@ impl EntityHandler {
         &self,
         entity_id: impl Into<EntityID>,
     ) -> Result<Self, Error> {
-        let entity_id = entity_id.into();
-        let mutations = vec![
-            kvrpcpb::Mutation {
-                key: relation_edge_path(
-                    T::type_path(),
-                    &self.entity_id,
-                    &entity_id,
-                    RelationDirection::In,
-                )
-                .into(),
-                op: kvrpcpb::Op::Del.into(),
-                ..Default::default()
-            },
-            kvrpcpb::Mutation {
-                key: relation_edge_path(
-                    T::type_path(),
-                    &entity_id,
-                    &self.entity_id,
-                    RelationDirection::Out,
-                )
-                .into(),
-                op: kvrpcpb::Op::Del.into(),
-                ..Default::default()
-            },
-            kvrpcpb::Mutation {
-                key: relation_data_path(T::type_path(), &self.entity_id, &entity_id).into(),
-                op: kvrpcpb::Op::Del.into(),
-                ..Default::default()
-            },
-        ];
+        let entity_id = &entity_id.into();
+        self.client
+            .run_optimistic(|txn| async move {
+                self.unlink_in_txn::<T>(&mut **txn.lock().await, entity_id)
+                    .await
+            })
+            .await?;
+        Ok(self.clone())
+    }
 
-        let mut txn = self
-            .client
-            .begin_optimistic()
+    /// 读取本实体通过 `link` 关联到 `entity_id` 的关系数据，反方向的关系需要从另一端读取，
+    /// 对称关系从任意一端读取的都是同一份数据
+    pub async fn relation<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<Option<T>, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        self.relation_in_txn(&mut *snapshot, &entity_id.into())
             .await
-            .map_err(Error::TikvError)?;
+    }
 
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
+    /// 本实体是否通过 `link` 关联到了 `entity_id`
+    pub async fn has_relation<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<bool, Error> {
+        let ((a, b), _, _) = relation_layout(
+            T::symmetric(),
+            &self.entity_id,
+            &entity_id.into(),
+            RelationDirection::In,
+        );
+        let mut snapshot = self.client.snapshot().await?;
+        Ok(snapshot
+            .get(relation_data_path(T::type_path(), &a, &b).into())
+            .await?
+            .is_some())
+    }
 
-        txn.commit().await.map_err(Error::TikvError)?;
-        Ok(self.clone())
+    /// 在同一个事务中读取关系数据、调用 `f` 修改后写回，并维护关系索引，关系不存在时返回 [`Error::NotFound`]
+    ///
+    /// 发生冲突时会重新读取并再次调用 `f`，因此 `f` 中不应包含副作用
+    pub async fn update_relation<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        f: impl Fn(&mut T),
+    ) -> Result<T, Error> {
+        let entity_id = &entity_id.into();
+        let f = &f;
+        self.client
+            .run_optimistic(|txn| async move {
+                self.update_relation_in_txn(&mut **txn.lock().await, entity_id, f)
+                    .await
+            })
+            .await
     }
 
+    /// 对称关系不区分方向，任意 `direction` 都返回全部相邻实体，方向为 [`RelationDirection::Both`]
     pub async fn edges<T: KvRelation + prost::Message + Default + 'static>(
         &self,
         direction: RelationDirection,
@@ -250,15 +305,15 @@ impl EntityHandler {
 
         let client = self.client.clone();
         let self_entity_id = self.entity_id.clone();
+        let symmetric = T::symmetric();
+        let direction = if symmetric {
+            RelationDirection::Both
+        } else {
+            direction
+        };
 
         Box::pin(try_stream! {
-            let mut snapshot = client.snapshot(
-                client
-                    .current_timestamp()
-                    .await
-                    .map_err(Error::TikvError)?,
-                tikv_client::TransactionOptions::new_optimistic(),
-            );
+            let mut snapshot = client.snapshot().await?;
             let mut start_key: Key =
                 relation_edge_path(T::type_path(), &self_entity_id, &EntityID::Empty, direction).into();
             let end_key: Key =
@@ -266,49 +321,43 @@ impl EntityHandler {
             loop {
                 let kvs = snapshot
                     .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                    .await
-                    .map_err(Error::TikvError)?
-                    .collect::<Vec<_>>();
+                    .await?;
                 if kvs.is_empty() {
                     break;
                 }
-                start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.clone());
+                start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.clone());
                 let len = kvs.len();
 
-                let mut data_keys = Vec::new();
+                let mut neighbors = Vec::with_capacity(len);
 
                 for k in kvs {
                     let key = key_to_string(&k)?;
                     let entity_id = EntityID::new_raw(key.split("/").nth(5).ok_or(Error::NotFound)?.to_string());
-                    let direction = key.split("/").nth(4).ok_or(Error::NotFound)?.to_string();
-
-                    let data_key = match direction.as_str() {
-                        "in" => relation_data_path(T::type_path(), &self_entity_id, &entity_id),
-                        "out" => relation_data_path(T::type_path(), &entity_id, &self_entity_id),
+                    let direction = match key.split("/").nth(4).ok_or(Error::NotFound)? {
+                        _ if symmetric => RelationDirection::Both,
+                        "in" => RelationDirection::In,
+                        "out" => RelationDirection::Out,
                         _ => unreachable!(),
                     };
-                    data_keys.push(data_key);
-                }
 
-                let data_values = snapshot.batch_get(data_keys).await.map_err(Error::TikvError)?;
-
-                for data in data_values {
-                    let key = key_to_string(&data.key())?;
-                    let a = EntityID::new_raw(key.split("/").nth(3).ok_or(Error::NotFound)?.to_string());
-                    let b = EntityID::new_raw(key.split("/").nth(4).ok_or(Error::NotFound)?.to_string());
-
-                    let entity_id;
-                    let direction;
+                    let ((a, b), _, _) = relation_layout(symmetric, &self_entity_id, &entity_id, direction);
+                    let data_key: Key = relation_data_path(T::type_path(), &a, &b).into();
+                    neighbors.push((data_key, entity_id, direction));
+                }
 
-                    if self_entity_id == a {
-                        direction = RelationDirection::In;
-                        entity_id = b.clone();
-                    } else {
-                        direction = RelationDirection::Out;
-                        entity_id = a.clone();
-                    }
+                // batch_get 不保证返回顺序，按边的扫描顺序输出
+                let mut data_values: HashMap<Key, _> = snapshot
+                    .batch_get(neighbors.iter().map(|(data_key, _, _)| data_key.clone()).collect())
+                    .await?
+                    .into_iter()
+                    .map(|data| (data.0, data.1))
+                    .collect();
 
-                    let value = T::decode(data.value().as_slice()).map_err(Error::DeserializationError)?;
+                for (data_key, entity_id, direction) in neighbors {
+                    let Some(data) = data_values.remove(&data_key) else {
+                        continue;
+                    };
+                    let value = T::decode(data.as_slice()).map_err(Error::DeserializationError)?;
                     yield (entity_id, direction, value);
                 }
                 if len < PAGE_SIZE {
@@ -322,184 +371,671 @@ impl EntityHandler {
         &self,
         direction: RelationDirection,
     ) -> Result<Vec<(EntityID, RelationDirection)>, Error> {
-        let mut txn = self
-            .client
-            .begin_optimistic()
+        self.client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                self.edges_entity_in_txn(T::type_path(), direction, &mut **txn)
+                    .await
+            })
+            .await
+    }
+
+    /// 本实体 `direction` 方向上 `T` 关系的数量，读取计数器而不扫描边，`Both` 为两个方向之和，
+    /// 对称关系不区分方向
+    pub async fn degree<T: KvRelation + prost::Message + Default>(
+        &self,
+        direction: RelationDirection,
+    ) -> Result<u64, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        self.degree_in_txn(T::type_path(), direction, &mut *snapshot)
             .await
-            .map_err(Error::TikvError)?;
-        let edges = self
-            .edges_entity_in_txn(T::type_path(), direction, &mut txn)
-            .await?;
-        txn.commit().await.map_err(Error::TikvError)?;
-        Ok(edges)
     }
 
     pub async fn delete_edges<T: KvRelation + prost::Message + Default>(
         &self,
     ) -> Result<Self, Error> {
-        let mut txn = self
-            .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
-        let edges = self
-            .edges_entity_in_txn(T::type_path(), RelationDirection::Both, &mut txn)
-            .await?;
+        self.client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                let edges = self
+                    .edges_entity_in_txn(T::type_path(), RelationDirection::Both, &mut **txn)
+                    .await?;
 
-        let mut mutations = Vec::new();
+                let mut mutations = Vec::new();
 
-        for (entity_id, direction) in edges {
-            mutations.extend(vec![
-                kvrpcpb::Mutation {
-                    key: relation_edge_path(T::type_path(), &self.entity_id, &entity_id, direction)
-                        .into(),
-                    op: kvrpcpb::Op::Del.into(),
-                    ..Default::default()
-                },
-                kvrpcpb::Mutation {
-                    key: relation_edge_path(T::type_path(), &entity_id, &self.entity_id, direction)
-                        .into(),
-                    op: kvrpcpb::Op::Del.into(),
-                    ..Default::default()
-                },
-            ]);
-            match direction {
-                RelationDirection::In => {
-                    mutations.push(kvrpcpb::Mutation {
-                        key: relation_data_path(T::type_path(), &self.entity_id, &entity_id).into(),
-                        op: kvrpcpb::Op::Del.into(),
-                        ..Default::default()
-                    });
-                }
-                RelationDirection::Out => {
-                    mutations.push(kvrpcpb::Mutation {
-                        key: relation_data_path(T::type_path(), &entity_id, &self.entity_id).into(),
-                        op: kvrpcpb::Op::Del.into(),
-                        ..Default::default()
-                    });
+                let layouts = edges
+                    .iter()
+                    .map(|(entity_id, direction)| {
+                        relation_layout(T::symmetric(), &self.entity_id, entity_id, *direction)
+                    })
+                    .collect::<Vec<_>>();
+                let relations = layouts
+                    .iter()
+                    .map(|(relation, _, _)| relation.clone())
+                    .collect::<Vec<_>>();
+                remove_relation_index_in_txn(
+                    &mut **txn,
+                    T::type_path(),
+                    &relations,
+                    &mut mutations,
+                )
+                .await?;
+                remove_relation_single(
+                    T::type_path(),
+                    T::cardinality(),
+                    T::symmetric(),
+                    &relations,
+                    &mut mutations,
+                );
+
+                let mut degrees = DegreeChanges::default();
+                for ((entity_id, _), ((a, b), self_direction, other_direction)) in
+                    edges.into_iter().zip(layouts)
+                {
+                    degrees.remove_edge(
+                        T::type_path(),
+                        &self.entity_id,
+                        &entity_id,
+                        self_direction,
+                        other_direction,
+                    );
+                    mutations.extend(vec![
+                        kvrpcpb::Mutation {
+                            key: relation_edge_path(
+                                T::type_path(),
+                                &self.entity_id,
+                                &entity_id,
+                                self_direction,
+                            )
+                            .into(),
+                            op: kvrpcpb::Op::Del.into(),
+                            ..Default::default()
+                        },
+                        kvrpcpb::Mutation {
+                            key: relation_edge_path(
+                                T::type_path(),
+                                &entity_id,
+                                &self.entity_id,
+                                other_direction,
+                            )
+                            .into(),
+                            op: kvrpcpb::Op::Del.into(),
+                            ..Default::default()
+                        },
+                        kvrpcpb::Mutation {
+                            key: relation_data_path(T::type_path(), &a, &b).into(),
+                            op: kvrpcpb::Op::Del.into(),
+                            ..Default::default()
+                        },
+                    ]);
                 }
-                RelationDirection::Both => {
-                    unreachable!();
+                degrees.apply_in_txn(&mut **txn, &mut mutations).await?;
+                txn.batch_mutate(mutations).await?;
+                if relations.is_empty() {
+                    return Ok(());
                 }
-            }
-        }
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
-        txn.commit().await.map_err(Error::TikvError)?;
+                self.bump_revision(&mut **txn).await
+            })
+            .await?;
         Ok(self.clone())
     }
 }
 
 impl EntityHandler {
-    pub(super) async fn get_metadata(
+    pub(crate) async fn get_in_txn<T: KvComponent + prost::Message + Default>(
         &self,
-        txn: &mut tikv_client::Transaction,
-    ) -> Result<Option<EntityMetadata>, Error> {
-        let Some(metadata) = txn
-            .get(entity_metadata_path(&self.entity_id))
-            .await
-            .map_err(Error::TikvError)?
+        txn: &mut dyn Snapshot,
+    ) -> Result<Option<T>, Error> {
+        let Some(data) = txn
+            .get(component_data_path(T::type_path(), &self.entity_id).into())
+            .await?
         else {
             return Ok(None);
         };
 
-        Ok(Some(
-            EntityMetadata::decode(metadata.as_slice()).map_err(Error::DeserializationError)?,
-        ))
+        let message = T::decode(data.as_slice()).map_err(Error::DeserializationError)?;
+
+        Ok(Some(message))
     }
 
-    pub(super) async fn update_metadata(
+    pub(crate) async fn get_many_in_txn<S: ComponentSet>(
         &self,
-        txn: &mut tikv_client::Transaction,
-        metadata: EntityMetadata,
-    ) -> Result<(), Error> {
-        txn.put(
-            entity_metadata_path(&self.entity_id),
-            metadata.encode_to_vec(),
-        )
-        .await
-        .map_err(Error::TikvError)?;
-        Ok(())
+        txn: &mut dyn Snapshot,
+    ) -> Result<S::Output, Error> {
+        let keys: Vec<Key> = S::type_paths()
+            .into_iter()
+            .map(|type_path| component_data_path(type_path, &self.entity_id).into())
+            .collect();
+        let values = txn
+            .batch_get(keys.clone())
+            .await?
+            .into_iter()
+            .map(|kv| (kv.key().clone(), kv.into_value()))
+            .collect::<HashMap<_, _>>();
+
+        S::decode(keys.iter().map(|key| values.get(key).cloned()).collect())
     }
 
-    pub(super) async fn delete_metadata(
+    pub(crate) async fn attach_in_txn(
         &self,
-        txn: &mut tikv_client::Transaction,
+        txn: &mut dyn Transaction,
+        bundle: impl ComponentBundle,
+        mode: AttachMode,
     ) -> Result<(), Error> {
-        txn.delete(entity_metadata_path(&self.entity_id))
-            .await
-            .map_err(Error::TikvError)?;
-        Ok(())
+        let mut mutations = Vec::new();
+        bundle.attach_to(self, txn, &mut mutations, mode).await?;
+        txn.batch_mutate(mutations).await
     }
 
-    pub(crate) async fn attach_component_in_txn<T: KvComponent + prost::Message + Default>(
+    /// 读取组件并调用 `f` 修改后通过 [`EntityHandler::attach_in_txn`] 写回，同时维护索引
+    pub(crate) async fn update_in_txn<T>(
         &self,
-        mutations: &mut Vec<kvrpcpb::Mutation>,
-        metadata: &mut EntityMetadata,
-        value: T,
-    ) -> Result<(), Error> {
-        {
-            let indexed_fields = value
-                .indexed_fields()
-                .into_iter()
-                .collect::<std::collections::HashMap<String, String>>();
-
-            let default_archetype = ComponentArchetype {
-                index_keys: T::indexed_field_names()
-                    .into_iter()
-                    .map(|k| (k.to_string(), "".to_string()))
-                    .collect(),
-            };
+        txn: &mut dyn Transaction,
+        default: impl FnOnce() -> Option<T>,
+        f: impl FnOnce(&mut T),
+    ) -> Result<T, Error>
+    where
+        T: KvComponent + prost::Message + Default + Clone,
+    {
+        let mut value = match self.get_in_txn::<T>(txn).await? {
+            Some(value) => value,
+            None => default().ok_or(Error::NotFound)?,
+        };
+        f(&mut value);
+        self.attach_in_txn(txn, value.clone(), AttachMode::Upsert)
+            .await?;
+        Ok(value)
+    }
 
-            for (field, key) in metadata
-                .component_archetypes
-                .entry(T::type_path().0.to_string())
-                .or_insert(default_archetype)
-                .index_keys
-                .iter_mut()
-            {
-                if !key.is_empty() {
-                    mutations.push(kvrpcpb::Mutation {
-                        key: component_index_path(T::type_path(), field, key, &self.entity_id)
-                            .into(),
-                        op: kvrpcpb::Op::Del.into(),
-                        ..Default::default()
-                    });
+    pub(crate) async fn detach_in_txn<T: KvComponent + prost::Message + Default>(
+        &self,
+        txn: &mut dyn Transaction,
+    ) -> Result<(), Error> {
+        let archetype = match self.get_metadata(txn).await? {
+            Some(mut metadata) => match metadata.component_archetypes.remove(T::type_path().0) {
+                Some(archetype) => {
+                    self.update_metadata(txn, metadata).await?;
+                    Some(archetype)
                 }
-                let Some(value) = indexed_fields.get(field.as_str()) else {
-                    continue;
-                };
-                let new_key = component_index_path(T::type_path(), field, value, &self.entity_id);
-                mutations.push(kvrpcpb::Mutation {
-                    key: new_key.clone().into(),
-                    op: kvrpcpb::Op::Put.into(),
-                    value: Into::<String>::into(self.entity_id.clone()).into(),
-                    ..Default::default()
-                });
-                *key = value.clone();
+                None => None,
+            },
+            None => None,
+        };
+        match archetype {
+            Some(archetype) => {
+                let mut mutations = Vec::new();
+                self.remove_component_index(T::type_path(), &archetype, &mut mutations);
+                txn.batch_mutate(mutations).await?;
             }
+            None if !T::indexed_field_names().is_empty() => return Err(Error::NotFound),
+            None => {}
         }
 
-        let data = value.encode_to_vec();
-
-        mutations.push(kvrpcpb::Mutation {
-            key: component_data_path(T::type_path(), &self.entity_id).into(),
-            op: kvrpcpb::Op::Put.into(),
-            value: data.into(),
-            ..Default::default()
-        });
-
-        Ok(())
+        txn.delete(component_data_path(T::type_path(), &self.entity_id).into())
+            .await
     }
 
-    async fn edges_entity_in_txn(
+    pub(crate) async fn link_in_txn<T: KvRelation + prost::Message + Default>(
+        &self,
+        txn: &mut dyn Transaction,
+        entity_id: &EntityID,
+        value: &T,
+        replace: bool,
+    ) -> Result<(), Error> {
+        if self.client.strict_links {
+            self.check_link_endpoints_in_txn(txn, entity_id).await?;
+        }
+        let ((a, b), self_direction, other_direction) = relation_layout(
+            T::symmetric(),
+            &self.entity_id,
+            entity_id,
+            RelationDirection::In,
+        );
+        let cardinality = T::cardinality();
+        if cardinality.limits_from() {
+            let key = relation_single_path(T::type_path(), &self.entity_id, self_direction);
+            if let Some(existing_entity) = get_single_in_txn(txn, &key, entity_id).await? {
+                if !replace {
+                    return Err(Error::CardinalityViolation {
+                        relation: T::type_path().0.to_string(),
+                        existing_entity,
+                    });
+                }
+                self.unlink_in_txn::<T>(txn, &existing_entity).await?;
+            }
+            txn.put(key.into(), Into::<String>::into(entity_id.clone()).into())
+                .await?;
+        }
+        if cardinality.limits_to() {
+            let key = relation_single_path(T::type_path(), entity_id, other_direction);
+            if let Some(existing_entity) = get_single_in_txn(txn, &key, &self.entity_id).await? {
+                if !replace {
+                    return Err(Error::CardinalityViolation {
+                        relation: T::type_path().0.to_string(),
+                        existing_entity,
+                    });
+                }
+                self.client
+                    .entity(existing_entity)
+                    .unlink_in_txn::<T>(txn, entity_id)
+                    .await?;
+            }
+            txn.put(
+                key.into(),
+                Into::<String>::into(self.entity_id.clone()).into(),
+            )
+            .await?;
+        }
+
+        let mut mutations = Vec::new();
+        let edge_key =
+            relation_edge_path(T::type_path(), &self.entity_id, entity_id, self_direction);
+        if txn.get(edge_key.into()).await?.is_none() {
+            let mut degrees = DegreeChanges::default();
+            degrees.add_edge(
+                T::type_path(),
+                &self.entity_id,
+                entity_id,
+                self_direction,
+                other_direction,
+            );
+            degrees.apply_in_txn(txn, &mut mutations).await?;
+        }
+        let relation = (a.clone(), b.clone());
+        remove_relation_index_in_txn(txn, T::type_path(), &[relation], &mut mutations).await?;
+        for (field, field_value) in value.indexed_fields() {
+            mutations.push(kvrpcpb::Mutation {
+                key: relation_index_path(T::type_path(), &field, &field_value, &a, &b).into(),
+                op: kvrpcpb::Op::Put.into(),
+                value: [].into(),
+                ..Default::default()
+            });
+        }
+        mutations.extend(vec![
+            kvrpcpb::Mutation {
+                key: relation_edge_path(T::type_path(), &self.entity_id, entity_id, self_direction)
+                    .into(),
+                op: kvrpcpb::Op::Put.into(),
+                value: [].into(),
+                ..Default::default()
+            },
+            kvrpcpb::Mutation {
+                key: relation_edge_path(
+                    T::type_path(),
+                    entity_id,
+                    &self.entity_id,
+                    other_direction,
+                )
+                .into(),
+                op: kvrpcpb::Op::Put.into(),
+                value: [].into(),
+                ..Default::default()
+            },
+            kvrpcpb::Mutation {
+                key: relation_data_path(T::type_path(), &a, &b).into(),
+                op: kvrpcpb::Op::Put.into(),
+                value: value.encode_to_vec(),
+                ..Default::default()
+            },
+        ]);
+        txn.batch_mutate(mutations).await?;
+        self.bump_revision(txn).await
+    }
+
+    /// 检查关系两端的实体是否存在，两端的元数据都通过 `get_for_update` 读取并锁定
+    async fn check_link_endpoints_in_txn(
+        &self,
+        txn: &mut dyn Transaction,
+        entity_id: &EntityID,
+    ) -> Result<(), Error> {
+        for entity_id in [&self.entity_id, entity_id] {
+            txn.get_for_update(entity_metadata_path(entity_id).into())
+                .await?
+                .ok_or(Error::NotFound)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn relation_in_txn<T: KvRelation + prost::Message + Default>(
+        &self,
+        txn: &mut dyn Snapshot,
+        entity_id: &EntityID,
+    ) -> Result<Option<T>, Error> {
+        let ((a, b), _, _) = relation_layout(
+            T::symmetric(),
+            &self.entity_id,
+            entity_id,
+            RelationDirection::In,
+        );
+        let Some(data) = txn
+            .get(relation_data_path(T::type_path(), &a, &b).into())
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(
+            T::decode(data.as_slice()).map_err(Error::DeserializationError)?,
+        ))
+    }
+
+    /// 读取关系数据并调用 `f` 修改后通过 [`EntityHandler::link_in_txn`] 写回
+    pub(crate) async fn update_relation_in_txn<T: KvRelation + prost::Message + Default>(
+        &self,
+        txn: &mut dyn Transaction,
+        entity_id: &EntityID,
+        f: impl FnOnce(&mut T),
+    ) -> Result<T, Error> {
+        let mut value = self
+            .relation_in_txn::<T>(txn, entity_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        f(&mut value);
+        self.link_in_txn(txn, entity_id, &value, false).await?;
+        Ok(value)
+    }
+
+    pub(crate) async fn unlink_in_txn<T: KvRelation + prost::Message + Default>(
+        &self,
+        txn: &mut dyn Transaction,
+        entity_id: &EntityID,
+    ) -> Result<(), Error> {
+        let ((a, b), self_direction, other_direction) = relation_layout(
+            T::symmetric(),
+            &self.entity_id,
+            entity_id,
+            RelationDirection::In,
+        );
+        let mut mutations = Vec::new();
+        let edge_key =
+            relation_edge_path(T::type_path(), &self.entity_id, entity_id, self_direction);
+        // 边不存在时不能删除基数记录，否则会释放另一条边占用的位置
+        if txn.get(edge_key.into()).await?.is_some() {
+            let mut degrees = DegreeChanges::default();
+            degrees.remove_edge(
+                T::type_path(),
+                &self.entity_id,
+                entity_id,
+                self_direction,
+                other_direction,
+            );
+            degrees.apply_in_txn(txn, &mut mutations).await?;
+            let relations = [(a.clone(), b.clone())];
+            remove_relation_index_in_txn(txn, T::type_path(), &relations, &mut mutations).await?;
+            remove_relation_single(
+                T::type_path(),
+                T::cardinality(),
+                T::symmetric(),
+                &relations,
+                &mut mutations,
+            );
+        }
+        mutations.extend(vec![
+            kvrpcpb::Mutation {
+                key: relation_edge_path(T::type_path(), &self.entity_id, entity_id, self_direction)
+                    .into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            },
+            kvrpcpb::Mutation {
+                key: relation_edge_path(
+                    T::type_path(),
+                    entity_id,
+                    &self.entity_id,
+                    other_direction,
+                )
+                .into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            },
+            kvrpcpb::Mutation {
+                key: relation_data_path(T::type_path(), &a, &b).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            },
+        ]);
+        txn.batch_mutate(mutations).await?;
+        self.bump_revision(txn).await
+    }
+
+    pub(super) async fn get_metadata(
+        &self,
+        txn: &mut dyn Transaction,
+    ) -> Result<Option<EntityMetadata>, Error> {
+        let Some(metadata) = txn
+            .get(entity_metadata_path(&self.entity_id).into())
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            EntityMetadata::decode(metadata.as_slice()).map_err(Error::DeserializationError)?,
+        ))
+    }
+
+    /// 写入元数据并递增实体的版本号，新建的元数据从实体上次删除时的版本号继续递增
+    pub(super) async fn update_metadata(
+        &self,
+        txn: &mut dyn Transaction,
+        mut metadata: EntityMetadata,
+    ) -> Result<(), Error> {
+        if metadata.revision == 0 {
+            metadata.revision = self.take_revision_floor(txn).await?;
+        }
+        metadata.revision += 1;
+        self.put_metadata(txn, &metadata).await
+    }
+
+    /// 读取并删除实体删除时留下的版本号下限，没有时为 0
+    async fn take_revision_floor(&self, txn: &mut dyn Transaction) -> Result<u64, Error> {
+        let key = entity_revision_path(&self.entity_id);
+        let Some(value) = txn.get(key.clone().into()).await? else {
+            return Ok(0);
+        };
+        txn.delete(key.into()).await?;
+        String::from_utf8(value)
+            .map_err(Error::InvalidUtf8)?
+            .parse()
+            .map_err(Error::InvalidU64)
+    }
+
+    /// 写入元数据，不改变版本号，用于重建索引等不影响实体数据的修改
+    pub(crate) async fn put_metadata(
+        &self,
+        txn: &mut dyn Transaction,
+        metadata: &EntityMetadata,
+    ) -> Result<(), Error> {
+        txn.put(
+            entity_metadata_path(&self.entity_id).into(),
+            metadata.encode_to_vec(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// 递增实体的版本号，实体没有元数据时不做任何修改
+    async fn bump_revision(&self, txn: &mut dyn Transaction) -> Result<(), Error> {
+        let Some(metadata) = self.get_metadata(txn).await? else {
+            return Ok(());
+        };
+        self.update_metadata(txn, metadata).await
+    }
+
+    pub(crate) async fn revision_in_txn(&self, txn: &mut dyn Snapshot) -> Result<u64, Error> {
+        let Some(metadata) = txn
+            .get(entity_metadata_path(&self.entity_id).into())
+            .await?
+        else {
+            return Ok(0);
+        };
+        Ok(EntityMetadata::decode(metadata.as_slice())
+            .map_err(Error::DeserializationError)?
+            .revision)
+    }
+
+    /// 删除元数据，并留下递增后的版本号，使重新创建的实体不会重复使用已经出现过的版本号
+    ///
+    /// 留下的版本号在实体重新创建时由 [`EntityHandler::update_metadata`] 取走并删除
+    pub(super) async fn delete_metadata(
+        &self,
+        txn: &mut dyn Transaction,
+        metadata: &EntityMetadata,
+    ) -> Result<(), Error> {
+        txn.delete(entity_metadata_path(&self.entity_id).into())
+            .await?;
+        txn.put(
+            entity_revision_path(&self.entity_id).into(),
+            (metadata.revision + 1).to_string().into(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn attach_component_in_txn<T: KvComponent + prost::Message + Default>(
+        &self,
+        txn: &mut dyn Transaction,
+        mutations: &mut Vec<kvrpcpb::Mutation>,
+        metadata: &mut EntityMetadata,
+        value: T,
+    ) -> Result<(), Error> {
+        self.reindex_component(
+            txn,
+            T::type_path(),
+            value.indexed_fields(),
+            &T::unique_field_names(),
+            mutations,
+            metadata,
+        )
+        .await?;
+
+        let data = value.encode_to_vec();
+
+        mutations.push(kvrpcpb::Mutation {
+            key: component_data_path(T::type_path(), &self.entity_id).into(),
+            op: kvrpcpb::Op::Put.into(),
+            value: data,
+            ..Default::default()
+        });
+
+        Ok(())
+    }
+
+    /// 删除 metadata 中记录的旧索引 key，写入 `indexed_fields` 对应的新索引 key，
+    /// 并用新的索引值替换组件的 `index_keys`
+    ///
+    /// `unique_fields` 中的字段会先通过 `get_for_update` 锁定并检查唯一索引 key，已被其他实体占用时返回 [`Error::UniqueViolation`]。
+    /// 唯一索引 key 直接写入事务，使同一事务中后续的检查能看到它
+    pub(crate) async fn reindex_component(
+        &self,
+        txn: &mut dyn Transaction,
+        type_path: TypePath,
+        indexed_fields: Vec<(String, String)>,
+        unique_fields: &[&str],
+        mutations: &mut Vec<kvrpcpb::Mutation>,
+        metadata: &mut EntityMetadata,
+    ) -> Result<(), Error> {
+        let unique_values = indexed_fields
+            .iter()
+            .filter(|(field, _)| unique_fields.contains(&field.as_str()))
+            .collect::<Vec<_>>();
+        for (field, value) in unique_values.iter() {
+            let Some(owner) = txn
+                .get_for_update(component_unique_path(type_path, field, value).into())
+                .await?
+            else {
+                continue;
+            };
+            let existing_entity =
+                EntityID::new_raw(String::from_utf8(owner).map_err(Error::InvalidUtf8)?);
+            if existing_entity != self.entity_id {
+                return Err(Error::UniqueViolation {
+                    field: field.clone(),
+                    value: value.clone(),
+                    existing_entity,
+                });
+            }
+        }
+
+        let archetype = metadata
+            .component_archetypes
+            .entry(type_path.0.to_string())
+            .or_default();
+
+        for (field, value) in archetype.index_keys.iter() {
+            mutations.push(kvrpcpb::Mutation {
+                key: component_index_path(type_path, field, value, &self.entity_id).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            });
+            if archetype.unique_fields.contains(field) {
+                txn.delete(component_unique_path(type_path, field, value).into())
+                    .await?;
+            }
+        }
+        for (field, value) in unique_values {
+            txn.put(
+                component_unique_path(type_path, field, value).into(),
+                Into::<String>::into(self.entity_id.clone()).into(),
+            )
+            .await?;
+        }
+
+        archetype.unique_fields = unique_fields
+            .iter()
+            .filter(|field| indexed_fields.iter().any(|(name, _)| name == *field))
+            .map(|field| field.to_string())
+            .collect();
+        archetype.index_keys = indexed_fields
+            .into_iter()
+            .map(|(field, value)| {
+                mutations.push(kvrpcpb::Mutation {
+                    key: component_index_path(type_path, &field, &value, &self.entity_id).into(),
+                    op: kvrpcpb::Op::Put.into(),
+                    value: Into::<String>::into(self.entity_id.clone()).into(),
+                    ..Default::default()
+                });
+                (field, value)
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// 删除组件的全部索引 key，包括唯一索引 key
+    fn remove_component_index(
+        &self,
+        type_path: TypePath,
+        archetype: &ComponentArchetype,
+        mutations: &mut Vec<kvrpcpb::Mutation>,
+    ) {
+        for (field, value) in archetype.index_keys.iter() {
+            mutations.push(kvrpcpb::Mutation {
+                key: component_index_path(type_path, field, value, &self.entity_id).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            });
+            if archetype.unique_fields.contains(field) {
+                mutations.push(kvrpcpb::Mutation {
+                    key: component_unique_path(type_path, field, value).into(),
+                    op: kvrpcpb::Op::Del.into(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    pub(crate) async fn edges_entity_in_txn(
         &self,
         type_path: TypePath,
         direction: RelationDirection,
-        txn: &mut tikv_client::Transaction,
+        txn: &mut dyn Snapshot,
     ) -> Result<Vec<(EntityID, RelationDirection)>, Error> {
         const PAGE_SIZE: usize = 128;
+        let symmetric = relation_symmetric(type_path);
+        let direction = if symmetric {
+            RelationDirection::Both
+        } else {
+            direction
+        };
         let mut start_key: Key =
             relation_edge_path(type_path, &self.entity_id, &EntityID::Empty, direction).into();
         let end_key: Key =
@@ -509,13 +1045,11 @@ impl EntityHandler {
         loop {
             let kvs = txn
                 .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                .await
-                .map_err(Error::TikvError)?
-                .collect::<Vec<_>>();
+                .await?;
             if kvs.is_empty() {
                 break;
             }
-            start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.clone());
+            start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.clone());
             let len = kvs.len();
 
             for k in kvs {
@@ -524,6 +1058,7 @@ impl EntityHandler {
                     EntityID::new_raw(key.split("/").nth(5).ok_or(Error::NotFound)?.to_string());
 
                 let direction = match key.split("/").nth(4).ok_or(Error::NotFound)? {
+                    _ if symmetric => RelationDirection::Both,
                     "in" => RelationDirection::In,
                     "out" => RelationDirection::Out,
                     _ => unreachable!(),
@@ -537,9 +1072,31 @@ impl EntityHandler {
         Ok(edges)
     }
 
+    pub(crate) async fn degree_in_txn(
+        &self,
+        type_path: TypePath,
+        direction: RelationDirection,
+        txn: &mut dyn Snapshot,
+    ) -> Result<u64, Error> {
+        let directions = match direction {
+            _ if relation_symmetric(type_path) => vec![RelationDirection::In],
+            RelationDirection::Both => vec![RelationDirection::In, RelationDirection::Out],
+            direction => vec![direction],
+        };
+        let mut degree = 0;
+        for direction in directions {
+            degree += get_degree_in_txn(
+                txn,
+                &relation_degree_path(type_path, &self.entity_id, direction),
+            )
+            .await?;
+        }
+        Ok(degree)
+    }
+
     async fn scan_edges_all_in_txn(
         &self,
-        txn: &mut tikv_client::Transaction,
+        txn: &mut dyn Transaction,
     ) -> Result<Vec<(EntityID, RelationDirection, TypePath)>, Error> {
         const PAGE_SIZE: usize = 128;
         let mut start_key: Key = relation_edge_no_type_path(&self.entity_id, TypePath("")).into();
@@ -549,14 +1106,12 @@ impl EntityHandler {
         loop {
             let kvs = txn
                 .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                .await
-                .map_err(Error::TikvError)?
-                .collect::<Vec<_>>();
+                .await?;
 
             if kvs.is_empty() {
                 break;
             }
-            start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.clone());
+            start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.clone());
             let len = kvs.len();
 
             for k in kvs {
@@ -567,6 +1122,7 @@ impl EntityHandler {
                     TypePath(intern_string(key.split("/").nth(3).ok_or(Error::NotFound)?));
 
                 let direction = match key.split("/").nth(4).ok_or(Error::NotFound)? {
+                    _ if relation_symmetric(type_path) => RelationDirection::Both,
                     "in" => RelationDirection::In,
                     "out" => RelationDirection::Out,
                     _ => unreachable!(),
@@ -580,146 +1136,560 @@ impl EntityHandler {
         Ok(edges)
     }
 
-    async fn delete_in_txn(
+    /// 删除实体，并按关系的 [`DeletePolicy`] 级联删除或拒绝删除
+    pub(crate) async fn delete_in_txn(
         &self,
-        mut txn: &mut tikv_client::Transaction,
+        txn: &mut dyn Transaction,
         mutations: &mut Vec<kvrpcpb::Mutation>,
     ) -> Result<(), Error> {
-        let Some(metadata) = self.get_metadata(&mut txn).await? else {
-            txn.rollback().await.map_err(Error::TikvError)?;
-            return Err(Error::NotFound);
-        };
-        for (component_type, component_archetype) in metadata.component_archetypes.iter() {
+        delete_entities_in_txn(
+            &self.client,
+            txn,
+            std::slice::from_ref(&self.entity_id),
+            mutations,
+        )
+        .await
+    }
+
+    /// 删除实体的组件、元数据和 `edges` 中的关系
+    async fn remove_in_txn(
+        &self,
+        txn: &mut dyn Transaction,
+        metadata: Option<EntityMetadata>,
+        edges: Vec<(EntityID, RelationDirection, TypePath)>,
+        degrees: &mut DegreeChanges,
+        mutations: &mut Vec<kvrpcpb::Mutation>,
+    ) -> Result<(), Error> {
+        for (component_type, component_archetype) in metadata
+            .iter()
+            .flat_map(|metadata| metadata.component_archetypes.iter())
+        {
             let component_type = TypePath(intern_string(component_type.as_str()));
-            for (field, key) in component_archetype.index_keys.iter() {
-                mutations.push(kvrpcpb::Mutation {
-                    key: component_index_path(component_type, field, key, &self.entity_id).into(),
-                    op: kvrpcpb::Op::Del.into(),
-                    ..Default::default()
-                });
-            }
+            self.remove_component_index(component_type, component_archetype, mutations);
             mutations.push(kvrpcpb::Mutation {
                 key: component_data_path(component_type, &self.entity_id).into(),
                 op: kvrpcpb::Op::Del.into(),
                 ..Default::default()
             });
         }
-        for (entity_id, direction, type_path) in self.scan_edges_all_in_txn(&mut txn).await? {
+        let mut relations = HashMap::<&str, Vec<(EntityID, EntityID)>>::new();
+        for (entity_id, direction, type_path) in edges {
+            let symmetric = relation_symmetric(type_path);
+            let ((a, b), self_direction, other_direction) =
+                relation_layout(symmetric, &self.entity_id, &entity_id, direction);
+            degrees.remove_edge(
+                type_path,
+                &self.entity_id,
+                &entity_id,
+                self_direction,
+                other_direction,
+            );
             mutations.push(kvrpcpb::Mutation {
-                key: relation_edge_path(type_path, &self.entity_id, &entity_id, direction).into(),
+                key: relation_edge_path(type_path, &self.entity_id, &entity_id, self_direction)
+                    .into(),
                 op: kvrpcpb::Op::Del.into(),
                 ..Default::default()
             });
             mutations.push(kvrpcpb::Mutation {
-                key: relation_edge_path(type_path, &entity_id, &self.entity_id, !direction).into(),
+                key: relation_edge_path(type_path, &entity_id, &self.entity_id, other_direction)
+                    .into(),
                 op: kvrpcpb::Op::Del.into(),
                 ..Default::default()
             });
-            match direction {
-                RelationDirection::In => {
-                    mutations.push(kvrpcpb::Mutation {
-                        key: relation_data_path(type_path, &self.entity_id, &entity_id).into(),
-                        op: kvrpcpb::Op::Del.into(),
-                        ..Default::default()
-                    });
-                }
-                RelationDirection::Out => {
-                    mutations.push(kvrpcpb::Mutation {
-                        key: relation_data_path(type_path, &entity_id, &self.entity_id).into(),
-                        op: kvrpcpb::Op::Del.into(),
-                        ..Default::default()
-                    });
+            mutations.push(kvrpcpb::Mutation {
+                key: relation_data_path(type_path, &a, &b).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            });
+            relations.entry(type_path.0).or_default().push((a, b));
+        }
+        for (type_path, relations) in relations {
+            let type_path = TypePath(type_path);
+            remove_relation_index_in_txn(txn, type_path, &relations, mutations).await?;
+            remove_relation_single(
+                type_path,
+                relation_cardinality(type_path),
+                relation_symmetric(type_path),
+                &relations,
+                mutations,
+            );
+        }
+        if let Some(metadata) = &metadata {
+            self.delete_metadata(txn, metadata).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 删除 `roots` 中的实体，以及通过 [`DeletePolicy::Cascade`] 关系级联到的实体
+///
+/// 被删除的实体仍被其他实体通过 [`DeletePolicy::Restrict`] 关系关联时返回 [`Error::DeleteRestricted`]，
+/// `roots` 中的实体不存在时返回 [`Error::NotFound`]，级联到的实体可以没有元数据
+pub(crate) async fn delete_entities_in_txn(
+    client: &DB,
+    txn: &mut dyn Transaction,
+    roots: &[EntityID],
+    mutations: &mut Vec<kvrpcpb::Mutation>,
+) -> Result<(), Error> {
+    let mut visited = HashSet::new();
+    let mut pending = roots.to_vec();
+    let mut entities = Vec::new();
+    while let Some(entity_id) = pending.pop() {
+        if !visited.insert(entity_id.clone()) {
+            continue;
+        }
+        let entity = client.entity(entity_id);
+        let metadata = entity.get_metadata(txn).await?;
+        if metadata.is_none() && roots.contains(&entity.entity_id) {
+            return Err(Error::NotFound);
+        }
+        let edges = entity.scan_edges_all_in_txn(txn).await?;
+        for (entity_id, direction, type_path) in edges.iter() {
+            // `in` 边表示本实体通过 `link` 关联到了对方
+            if relation_delete_policy(*type_path) == DeletePolicy::Cascade
+                && !matches!(direction, RelationDirection::Out)
+            {
+                pending.push(entity_id.clone());
+            }
+        }
+        entities.push((entity, metadata, edges));
+    }
+
+    for (_, _, edges) in entities.iter() {
+        for (entity_id, direction, type_path) in edges {
+            if relation_delete_policy(*type_path) == DeletePolicy::Restrict
+                && !matches!(direction, RelationDirection::In)
+                && !visited.contains(entity_id)
+            {
+                return Err(Error::DeleteRestricted {
+                    relation: type_path.0.to_string(),
+                    existing_entity: entity_id.clone(),
+                });
+            }
+        }
+    }
+
+    // 两端都被删除的关系在先删除的一端已经处理过
+    let mut removed = HashSet::new();
+    let mut degrees = DegreeChanges::default();
+    for (entity, metadata, edges) in entities {
+        let edges = edges
+            .into_iter()
+            .filter(|(entity_id, _, _)| !removed.contains(entity_id))
+            .collect();
+        entity
+            .remove_in_txn(txn, metadata, edges, &mut degrees, mutations)
+            .await?;
+        removed.insert(entity.entity_id);
+    }
+    degrees.apply_in_txn(txn, mutations).await
+}
+
+/// 一个事务中各实体关系数量的变化，同一条边只计一次
+#[derive(Default)]
+pub(crate) struct DegreeChanges {
+    edges: HashSet<String>,
+    deltas: HashMap<String, i64>,
+}
+
+impl DegreeChanges {
+    pub(crate) fn add_edge(
+        &mut self,
+        type_path: TypePath,
+        entity_id: &EntityID,
+        other: &EntityID,
+        self_direction: RelationDirection,
+        other_direction: RelationDirection,
+    ) {
+        self.record(type_path, entity_id, other, self_direction, 1);
+        self.record(type_path, other, entity_id, other_direction, 1);
+    }
+
+    pub(crate) fn remove_edge(
+        &mut self,
+        type_path: TypePath,
+        entity_id: &EntityID,
+        other: &EntityID,
+        self_direction: RelationDirection,
+        other_direction: RelationDirection,
+    ) {
+        self.record(type_path, entity_id, other, self_direction, -1);
+        self.record(type_path, other, entity_id, other_direction, -1);
+    }
+
+    /// 对称关系的自环两端是同一条边，只计一次
+    fn record(
+        &mut self,
+        type_path: TypePath,
+        entity_id: &EntityID,
+        other: &EntityID,
+        direction: RelationDirection,
+        delta: i64,
+    ) {
+        if self
+            .edges
+            .insert(relation_edge_path(type_path, entity_id, other, direction))
+        {
+            *self
+                .deltas
+                .entry(relation_degree_path(type_path, entity_id, direction))
+                .or_default() += delta;
+        }
+    }
+
+    /// 读取当前数量并写入变化后的值，数量为 0 时删除计数器
+    pub(crate) async fn apply_in_txn(
+        self,
+        txn: &mut dyn Transaction,
+        mutations: &mut Vec<kvrpcpb::Mutation>,
+    ) -> Result<(), Error> {
+        for (key, delta) in self.deltas {
+            if delta == 0 {
+                continue;
+            }
+            let degree = get_degree_in_txn(txn, &key)
+                .await?
+                .saturating_add_signed(delta);
+            mutations.push(if degree == 0 {
+                kvrpcpb::Mutation {
+                    key: key.into(),
+                    op: kvrpcpb::Op::Del.into(),
+                    ..Default::default()
                 }
-                RelationDirection::Both => {
-                    unreachable!();
+            } else {
+                kvrpcpb::Mutation {
+                    key: key.into(),
+                    op: kvrpcpb::Op::Put.into(),
+                    value: degree.to_string().into(),
+                    ..Default::default()
                 }
-            }
+            });
+        }
+        Ok(())
+    }
+}
+
+async fn get_degree_in_txn(txn: &mut dyn Snapshot, key: &str) -> Result<u64, Error> {
+    let Some(value) = txn.get(key.to_string().into()).await? else {
+        return Ok(0);
+    };
+    String::from_utf8(value)
+        .map_err(Error::InvalidUtf8)?
+        .parse()
+        .map_err(Error::InvalidU64)
+}
+
+/// 读取单边记录，记录的实体不是 `expected` 时返回该实体
+async fn get_single_in_txn(
+    txn: &mut dyn Transaction,
+    key: &str,
+    expected: &EntityID,
+) -> Result<Option<EntityID>, Error> {
+    let Some(value) = txn.get(key.to_string().into()).await? else {
+        return Ok(None);
+    };
+    let existing_entity = EntityID::new_raw(String::from_utf8(value).map_err(Error::InvalidUtf8)?);
+    Ok((existing_entity != *expected).then_some(existing_entity))
+}
+
+/// 删除受基数约束的关系的单边记录，`relations` 中的 `(a, b)` 对应 `relation/data/{type}/{a}/{b}`
+pub(crate) fn remove_relation_single(
+    type_path: TypePath,
+    cardinality: Cardinality,
+    symmetric: bool,
+    relations: &[(EntityID, EntityID)],
+    mutations: &mut Vec<kvrpcpb::Mutation>,
+) {
+    // 对称关系两端的单边记录都记为 `in`
+    let to_direction = if symmetric {
+        RelationDirection::In
+    } else {
+        RelationDirection::Out
+    };
+    for (a, b) in relations {
+        if cardinality.limits_from() {
+            mutations.push(kvrpcpb::Mutation {
+                key: relation_single_path(type_path, a, RelationDirection::In).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            });
         }
-        self.delete_metadata(&mut txn).await?;
+        if cardinality.limits_to() {
+            mutations.push(kvrpcpb::Mutation {
+                key: relation_single_path(type_path, b, to_direction).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// 删除关系数据对应的索引 key，`relations` 中的 `(a, b)` 对应 `relation/data/{type}/{a}/{b}`
+pub(crate) async fn remove_relation_index_in_txn(
+    txn: &mut dyn Transaction,
+    type_path: TypePath,
+    relations: &[(EntityID, EntityID)],
+    mutations: &mut Vec<kvrpcpb::Mutation>,
+) -> Result<(), Error> {
+    let Some(meta) = relation_meta(type_path.0) else {
+        return Ok(());
+    };
+    if relations.is_empty() || (meta.indexed_field_names)().is_empty() {
+        return Ok(());
+    }
+
+    let data = txn
+        .batch_get(
+            relations
+                .iter()
+                .map(|(a, b)| relation_data_path(type_path, a, b).into())
+                .collect(),
+        )
+        .await?;
+    for kv in data {
+        let key = key_to_string(kv.key())?;
+        let a = EntityID::new_raw(key.split("/").nth(3).ok_or(Error::NotFound)?.to_string());
+        let b = EntityID::new_raw(key.split("/").nth(4).ok_or(Error::NotFound)?.to_string());
+        for (field, value) in (meta.indexed_values)(kv.value())? {
+            mutations.push(kvrpcpb::Mutation {
+                key: relation_index_path(type_path, &field, &value, &a, &b).into(),
+                op: kvrpcpb::Op::Del.into(),
+                ..Default::default()
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 带版本号条件的实体写入，通过 [`EntityHandler::if_revision`] 获得
+///
+/// 版本号检查和写入在同一个事务中，并发修改会导致事务冲突，重试时重新检查版本号
+#[derive(Clone)]
+pub struct ConditionalEntityHandler {
+    entity: EntityHandler,
+    revision: u64,
+}
 
+impl ConditionalEntityHandler {
+    async fn check_revision_in_txn(&self, txn: &mut dyn Transaction) -> Result<(), Error> {
+        let actual = self.entity.revision_in_txn(txn).await?;
+        if actual != self.revision {
+            return Err(Error::RevisionMismatch {
+                expected: self.revision,
+                actual,
+            });
+        }
         Ok(())
     }
+
+    async fn attach_with_mode(
+        &self,
+        bundle: impl ComponentBundle,
+        mode: AttachMode,
+    ) -> Result<EntityHandler, Error> {
+        let bundle = &bundle;
+        self.entity
+            .client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                self.check_revision_in_txn(&mut **txn).await?;
+                self.entity
+                    .attach_in_txn(&mut **txn, bundle.clone(), mode)
+                    .await
+            })
+            .await?;
+        Ok(self.entity.clone())
+    }
+
+    pub async fn attach(&self, bundle: impl ComponentBundle) -> Result<EntityHandler, Error> {
+        self.attach_with_mode(bundle, AttachMode::Upsert).await
+    }
+
+    pub async fn insert(&self, bundle: impl ComponentBundle) -> Result<EntityHandler, Error> {
+        self.attach_with_mode(bundle, AttachMode::Insert).await
+    }
+
+    pub async fn replace(&self, bundle: impl ComponentBundle) -> Result<EntityHandler, Error> {
+        self.attach_with_mode(bundle, AttachMode::Replace).await
+    }
+
+    pub async fn detach<T: KvComponent + prost::Message + Default>(
+        &self,
+    ) -> Result<EntityHandler, Error> {
+        self.entity
+            .client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                self.check_revision_in_txn(&mut **txn).await?;
+                self.entity.detach_in_txn::<T>(&mut **txn).await
+            })
+            .await?;
+        Ok(self.entity.clone())
+    }
+
+    pub async fn delete(&self) -> Result<EntityHandler, Error> {
+        self.entity
+            .client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                self.check_revision_in_txn(&mut **txn).await?;
+                let mut mutations = Vec::new();
+                self.entity
+                    .delete_in_txn(&mut **txn, &mut mutations)
+                    .await?;
+                txn.batch_mutate(mutations).await
+            })
+            .await?;
+        Ok(self.entity.clone())
+    }
+
+    pub async fn link<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        value: T,
+    ) -> Result<EntityHandler, Error> {
+        let entity_id = &entity_id.into();
+        let value = &value;
+        self.entity
+            .client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                self.check_revision_in_txn(&mut **txn).await?;
+                self.entity
+                    .link_in_txn(&mut **txn, entity_id, value, false)
+                    .await
+            })
+            .await?;
+        Ok(self.entity.clone())
+    }
+
+    pub async fn unlink<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<EntityHandler, Error> {
+        let entity_id = &entity_id.into();
+        self.entity
+            .client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                self.check_revision_in_txn(&mut **txn).await?;
+                self.entity.unlink_in_txn::<T>(&mut **txn, entity_id).await
+            })
+            .await?;
+        Ok(self.entity.clone())
+    }
 }
 
 #[derive(Clone)]
 pub struct EntityListHandler {
     pub(crate) entity_ids: Vec<EntityID>,
-    pub(crate) client: TransactionClient,
+    pub(crate) client: DB,
 }
 
 impl EntityListHandler {
-    pub fn new(entity_ids: Vec<EntityID>, client: TransactionClient) -> Self {
+    pub fn new(entity_ids: Vec<EntityID>, client: DB) -> Self {
         Self { entity_ids, client }
     }
 
     pub async fn attach(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
-        let mut txn = self
+        let (handler, _) = self.attach_with_attempts(bundle).await?;
+        Ok(handler)
+    }
+
+    /// 同 [`EntityListHandler::attach`]，同时返回提交成功时一共尝试的次数
+    pub async fn attach_with_attempts(
+        &self,
+        bundle: impl ComponentBundle,
+    ) -> Result<(Self, u32), Error> {
+        let bundle = &bundle;
+        let (_, attempts) = self
             .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
-        let mut mutations = Vec::new();
-        for entity_id in self.entity_ids.iter() {
-            bundle
-                .clone()
-                .attach_to(
-                    &EntityHandler {
-                        entity_id: entity_id.clone(),
-                        client: self.client.clone(),
-                    },
-                    &mut txn,
-                    &mut mutations,
-                )
-                .await?;
-        }
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
-        txn.commit().await.map_err(Error::TikvError)?;
-        Ok(self.clone())
+            .run_optimistic_with_attempts(|txn| async move {
+                let mut txn = txn.lock().await;
+                let mut mutations = Vec::new();
+                for entity_id in self.entity_ids.iter() {
+                    bundle
+                        .clone()
+                        .attach_to(
+                            &EntityHandler {
+                                entity_id: entity_id.clone(),
+                                client: self.client.clone(),
+                            },
+                            &mut **txn,
+                            &mut mutations,
+                            AttachMode::Upsert,
+                        )
+                        .await?;
+                }
+                txn.batch_mutate(mutations).await
+            })
+            .await?;
+        Ok((self.clone(), attempts))
     }
 
     pub async fn get<T: KvComponent + prost::Message + Default>(&self) -> Result<Vec<T>, Error> {
-        let mut snapshot = self.client.snapshot(
-            self.client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
+        let mut snapshot = self.client.snapshot().await?;
 
         snapshot
             .batch_get(
                 self.entity_ids
                     .iter()
-                    .map(|id| component_data_path(T::type_path(), id))
+                    .map(|id| component_data_path(T::type_path(), id).into())
                     .collect::<Vec<_>>(),
             )
-            .await
-            .map_err(Error::TikvError)?
+            .await?
+            .into_iter()
             .map(|data| T::decode(data.value().as_slice()).map_err(Error::DeserializationError))
             .collect()
     }
 
-    pub async fn delete(&self) -> Result<Self, Error> {
-        let mut txn = self
-            .client
-            .begin_optimistic()
-            .await
-            .map_err(Error::TikvError)?;
-        let mut mutations = Vec::new();
-        for entity_id in self.entity_ids.iter() {
-            EntityHandler {
-                entity_id: entity_id.clone(),
-                client: self.client.clone(),
+    /// 按实体顺序逐页读取组件，没有该组件的实体返回 None
+    #[allow(clippy::type_complexity)]
+    pub fn stream<T: KvComponent + prost::Message + Default + 'static>(
+        &self,
+    ) -> std::pin::Pin<Box<dyn Stream<Item = Result<(EntityID, Option<T>), Error>> + Send>> {
+        const PAGE_SIZE: usize = 128;
+
+        let client = self.client.clone();
+        let entity_ids = self.entity_ids.clone();
+
+        Box::pin(try_stream! {
+            let mut snapshot = client.snapshot().await?;
+            for chunk in entity_ids.chunks(PAGE_SIZE) {
+                let mut values = snapshot
+                    .batch_get(
+                        chunk
+                            .iter()
+                            .map(|id| component_data_path(T::type_path(), id).into())
+                            .collect::<Vec<_>>(),
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|kv| (kv.key().clone(), kv.into_value()))
+                    .collect::<HashMap<_, _>>();
+
+                for entity_id in chunk {
+                    let value = match values.remove(&component_data_path(T::type_path(), entity_id).into()) {
+                        Some(data) => Some(T::decode(data.as_slice()).map_err(Error::DeserializationError)?),
+                        None => None,
+                    };
+                    yield (entity_id.clone(), value);
+                }
             }
-            .delete_in_txn(&mut txn, &mut mutations)
+        })
+    }
+
+    pub async fn delete(&self) -> Result<Self, Error> {
+        self.client
+            .run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                let mut mutations = Vec::new();
+                delete_entities_in_txn(&self.client, &mut **txn, &self.entity_ids, &mut mutations)
+                    .await?;
+                txn.batch_mutate(mutations).await
+            })
             .await?;
-        }
-        txn.batch_mutate(mutations)
-            .await
-            .map_err(Error::TikvError)?;
-        txn.commit().await.map_err(Error::TikvError)?;
         Ok(self.clone())
     }
 }
@@ -1,7 +1,9 @@
+use crate::db::EntityID;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("TiKV error: {0}")]
-    TikvError(#[from] tikv_client::Error),
+    TikvError(Box<tikv_client::Error>),
     #[error("Prost error: {0}")]
     SerializationError(#[from] prost::EncodeError),
     #[error("Prost error: {0}")]
@@ -12,6 +14,72 @@ pub enum Error {
     InvalidUtf8(std::string::FromUtf8Error),
     #[error("Invalid u64: {0}")]
     InvalidU64(std::num::ParseIntError),
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Invalid archetype: {0}")]
+    InvalidArchetype(String),
+    #[error("Invalid depth range: {0}")]
+    InvalidDepth(String),
     #[error("Not found")]
     NotFound,
+    #[error("Already exists")]
+    AlreadyExists,
+    #[error(
+        "Unique constraint violated on {field} = {value}, already owned by {existing_entity:?}"
+    )]
+    UniqueViolation {
+        field: String,
+        value: String,
+        existing_entity: EntityID,
+    },
+    #[error("Cardinality of relation {relation} violated, already linked with {existing_entity:?}")]
+    CardinalityViolation {
+        relation: String,
+        existing_entity: EntityID,
+    },
+    #[error("Relation {relation} restricts deletion, still linked from {existing_entity:?}")]
+    DeleteRestricted {
+        relation: String,
+        existing_entity: EntityID,
+    },
+    #[error("Revision mismatch, expected {expected} but found {actual}")]
+    RevisionMismatch { expected: u64, actual: u64 },
+    #[error("Traversal frontier exceeded the limit of {0} entities")]
+    TraversalLimitExceeded(usize),
+    #[error("Write conflict on key: {0}")]
+    WriteConflict(String),
+    #[error("Transaction failed after {attempts} attempts: {source}")]
+    RetryExhausted { attempts: u32, source: Box<Error> },
+}
+
+impl Error {
+    /// 是否为事务冲突等可以通过重新执行事务解决的错误
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::WriteConflict(_) => true,
+            Error::TikvError(error) => is_tikv_retryable(error),
+            _ => false,
+        }
+    }
+}
+
+fn is_tikv_retryable(error: &tikv_client::Error) -> bool {
+    match error {
+        tikv_client::Error::KeyError(key_error) => {
+            key_error.conflict.is_some()
+                || key_error.deadlock.is_some()
+                || key_error.locked.is_some()
+                || !key_error.retryable.is_empty()
+        }
+        tikv_client::Error::ResolveLockError(_) => true,
+        tikv_client::Error::MultipleKeyErrors(errors)
+        | tikv_client::Error::ExtractedErrors(errors) => errors.iter().any(is_tikv_retryable),
+        _ => false,
+    }
+}
+
+impl From<tikv_client::Error> for Error {
+    fn from(error: tikv_client::Error) -> Self {
+        Error::TikvError(Box::new(error))
+    }
 }
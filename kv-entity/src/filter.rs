@@ -1,24 +1,338 @@
-use std::marker::PhantomData;
+use std::{collections::HashMap, marker::PhantomData, ops::Bound, pin::Pin, str::FromStr};
 
+use async_stream::try_stream;
+use futures::Stream;
+
+use futures::future::BoxFuture;
 use tikv_client::Key;
 
 use crate::{
-    DB, KvComponent, component_data_path, component_index_path,
+    DB, KvComponent, KvRelation, TypePath,
+    backend::Snapshot,
+    component_data_path,
     db::EntityID,
     entity_handler::{EntityHandler, EntityListHandler},
     error::Error,
-    next_key,
+    key_after, next_key, relation_data_path,
+    utils::{component_index_field_path, key_to_string, relation_index_field_path},
 };
 
+/// 索引字段的检索条件，值均为编码后的字符串
 pub enum BoundCondition {
+    /// 等于
     Value(String),
+    /// 闭区间 `[start, end]`
     Range(String, String),
+    /// 上下界可以分别为包含、不包含或无界的区间
+    Bounds(Bound<String>, Bound<String>),
+    /// 以指定前缀开头
+    Prefix(String),
+}
+
+impl BoundCondition {
+    /// 检索条件对应的索引 key 区间 `[start, end)`，`field_prefix` 为索引字段的公共前缀
+    pub(crate) fn key_range(&self, field_prefix: &str) -> (Key, Key) {
+        let value_key = |value: &str| -> Key { format!("{}{}/", field_prefix, value).into() };
+        let field_key: Key = field_prefix.to_string().into();
+
+        match self {
+            BoundCondition::Value(value) => {
+                let key = value_key(value);
+                let end = next_key(&key);
+                (key, end)
+            }
+            BoundCondition::Range(start, end) => (value_key(start), next_key(&value_key(end))),
+            BoundCondition::Bounds(start, end) => {
+                let start = match start {
+                    Bound::Included(value) => value_key(value),
+                    Bound::Excluded(value) => next_key(&value_key(value)),
+                    Bound::Unbounded => field_key.clone(),
+                };
+                let end = match end {
+                    Bound::Included(value) => next_key(&value_key(value)),
+                    Bound::Excluded(value) => value_key(value),
+                    Bound::Unbounded => next_key(&field_key),
+                };
+                (start, end)
+            }
+            BoundCondition::Prefix(prefix) => {
+                let key: Key = format!("{}{}", field_prefix, prefix).into();
+                let end = next_key(&key);
+                (key, end)
+            }
+        }
+    }
+}
+
+/// 组件的检索条件，叶子节点为某个组件的一个索引字段上的检索
+enum Condition {
+    Index {
+        type_path: TypePath,
+        field_name: String,
+        bound_condition: BoundCondition,
+    },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    /// 计算满足条件的实体，叶子节点按索引顺序返回，组合条件按实体 ID 排序返回
+    ///
+    /// `universe` 为单独使用 `not` 时的全集，即拥有该组件的全部实体
+    fn query_entity_id_vec<'a>(
+        &'a self,
+        universe: TypePath,
+        snapshot: &'a mut dyn Snapshot,
+    ) -> BoxFuture<'a, Result<Vec<EntityID>, Error>> {
+        Box::pin(async move {
+            match self {
+                Condition::Index {
+                    type_path,
+                    field_name,
+                    bound_condition,
+                } => {
+                    let (start_key, end_key) = bound_condition
+                        .key_range(&component_index_field_path(*type_path, field_name));
+                    scan_entity_ids(snapshot, start_key, end_key, true).await
+                }
+                Condition::And(a, b) => match (a.as_ref(), b.as_ref()) {
+                    (x, Condition::Not(y)) | (Condition::Not(y), x) => {
+                        let x = sorted(x.query_entity_id_vec(universe, &mut *snapshot).await?);
+                        let y = sorted(y.query_entity_id_vec(universe, &mut *snapshot).await?);
+                        Ok(difference(x, &y))
+                    }
+                    (a, b) => {
+                        let a = sorted(a.query_entity_id_vec(universe, &mut *snapshot).await?);
+                        let b = sorted(b.query_entity_id_vec(universe, &mut *snapshot).await?);
+                        Ok(intersect(a, &b))
+                    }
+                },
+                Condition::Or(a, b) => {
+                    let a = sorted(a.query_entity_id_vec(universe, &mut *snapshot).await?);
+                    let b = sorted(b.query_entity_id_vec(universe, &mut *snapshot).await?);
+                    Ok(union(a, b))
+                }
+                Condition::Not(x) => {
+                    let all = scan_entity_ids(
+                        snapshot,
+                        component_data_path(universe, &EntityID::Empty).into(),
+                        component_data_path(universe, &EntityID::Max).into(),
+                        false,
+                    )
+                    .await?;
+                    let x = sorted(x.query_entity_id_vec(universe, &mut *snapshot).await?);
+                    Ok(difference(all, &x))
+                }
+            }
+        })
+    }
+}
+
+/// 结果的排列顺序，单个索引条件按索引值排序，组合条件按实体 ID 排序
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// 可恢复的分页游标，记录上一页最后一条结果的索引 key
+///
+/// 通过 `to_string` 编码为字符串交给客户端，下一次请求时用 `parse` 还原后传给 [`Filter::after`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor(Vec<u8>);
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.len().is_multiple_of(2) || !s.is_ascii() {
+            return Err(Error::InvalidCursor(s.to_string()));
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Cursor)
+            .map_err(|_| Error::InvalidCursor(s.to_string()))
+    }
+}
+
+/// 一页检索结果，`cursor` 为 None 表示没有更多结果
+pub struct Page<T> {
+    pub items: Vec<(EntityID, T)>,
+    pub cursor: Option<Cursor>,
+}
+
+/// 按 `order` 扫描 `[start_key, end_key)` 中的索引，跳过 `offset` 条后最多返回 `limit` 条
+async fn scan_index_page(
+    snapshot: &mut dyn Snapshot,
+    (mut start_key, mut end_key): (Key, Key),
+    order: Order,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<Vec<(Key, EntityID)>, Error> {
+    const PAGE_SIZE: usize = 128;
+
+    let mut skipped = 0;
+    let mut result = Vec::new();
+
+    while start_key < end_key {
+        let page_size = limit.map_or(PAGE_SIZE, |limit| {
+            (offset - skipped + limit - result.len()).min(PAGE_SIZE)
+        });
+        if page_size == 0 {
+            break;
+        }
+
+        let kvs = match order {
+            Order::Asc => {
+                snapshot
+                    .scan(start_key.clone()..end_key.clone(), page_size as u32)
+                    .await?
+            }
+            Order::Desc => {
+                snapshot
+                    .scan_reverse(start_key.clone()..end_key.clone(), page_size as u32)
+                    .await?
+            }
+        };
+
+        let Some(last) = kvs.last() else {
+            break;
+        };
+        match order {
+            Order::Asc => start_key = key_after(last.key()),
+            Order::Desc => end_key = last.key().clone(),
+        }
+        let len = kvs.len();
+
+        for kv in kvs {
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            let entity_id = String::from_utf8(kv.value().to_vec())
+                .map_err(|e| Error::InvalidEntityId(e.to_string()))?;
+            result.push((kv.into_key(), EntityID::new_raw(entity_id)));
+        }
+
+        if len < page_size {
+            break;
+        }
+    }
+    Ok(result)
 }
 
+/// 扫描 `[start_key, end_key)` 中的实体，`from_value` 为 true 时实体 ID 在 value 中，否则为 key 的最后一段
+async fn scan_entity_ids(
+    snapshot: &mut dyn Snapshot,
+    mut start_key: Key,
+    end_key: Key,
+    from_value: bool,
+) -> Result<Vec<EntityID>, Error> {
+    const PAGE_SIZE: usize = 128;
+
+    let mut entity_ids = Vec::new();
+
+    while start_key < end_key {
+        let kvs = snapshot
+            .scan(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
+            .await?;
+
+        if kvs.is_empty() {
+            break;
+        }
+
+        start_key = key_after(&kvs.last().ok_or(Error::NotFound)?.key().clone());
+        let len = kvs.len();
+
+        for kv in kvs {
+            let entity_id = if from_value {
+                String::from_utf8(kv.value().to_vec())
+                    .map_err(|e| Error::InvalidEntityId(e.to_string()))?
+            } else {
+                let key = key_to_string(kv.key())?;
+                key.rsplit('/').next().ok_or(Error::NotFound)?.to_string()
+            };
+            entity_ids.push(EntityID::new_raw(entity_id));
+        }
+
+        if len < PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(entity_ids)
+}
+
+fn sorted(mut entity_ids: Vec<EntityID>) -> Vec<EntityID> {
+    entity_ids.sort();
+    entity_ids.dedup();
+    entity_ids
+}
+
+/// 两个有序集合的交集
+fn intersect(a: Vec<EntityID>, b: &[EntityID]) -> Vec<EntityID> {
+    let mut b = b.iter().peekable();
+    a.into_iter()
+        .filter(|id| {
+            while b.next_if(|other| *other < id).is_some() {}
+            b.peek().is_some_and(|other| *other == id)
+        })
+        .collect()
+}
+
+/// 两个有序集合的并集
+fn union(a: Vec<EntityID>, b: Vec<EntityID>) -> Vec<EntityID> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if x < y => a.next(),
+            (Some(x), Some(y)) if x > y => b.next(),
+            (Some(_), Some(_)) => {
+                b.next();
+                a.next()
+            }
+            (Some(_), None) => a.next(),
+            (None, Some(_)) => b.next(),
+            (None, None) => break,
+        };
+        result.extend(next);
+    }
+    result
+}
+
+/// 两个有序集合的差集 `a - b`
+fn difference(a: Vec<EntityID>, b: &[EntityID]) -> Vec<EntityID> {
+    let mut b = b.iter().peekable();
+    a.into_iter()
+        .filter(|id| {
+            while b.next_if(|other| *other < id).is_some() {}
+            b.peek().is_none_or(|other| *other != id)
+        })
+        .collect()
+}
+
+/// 组件检索，结果为拥有组件 `T` 的实体，可以通过 `and`、`or`、`not` 与其他组件上的检索组合
 pub struct Filter<T> {
     client: DB,
-    field_name: String,
-    bound_condition: BoundCondition,
+    condition: Condition,
+    order: Order,
+    offset: usize,
+    limit: Option<usize>,
+    after: Option<Cursor>,
     _marker: PhantomData<T>,
 }
 
@@ -29,171 +343,421 @@ where
     pub fn new(client: DB, field_name: String, bound_condition: BoundCondition) -> Self {
         Self {
             client,
-            field_name,
-            bound_condition,
+            condition: Condition::Index {
+                type_path: T::type_path(),
+                field_name,
+                bound_condition,
+            },
+            order: Order::Asc,
+            offset: 0,
+            limit: None,
+            after: None,
             _marker: PhantomData,
         }
     }
 
-    async fn query_entity_id(
-        &self,
-        snapshot: &mut tikv_client::Snapshot,
-    ) -> Result<EntityID, Error> {
-        let (start, end) = match &self.bound_condition {
-            BoundCondition::Value(value) => (value, value),
-            BoundCondition::Range(start, end) => (start, end),
-        };
+    pub(crate) fn client(&self) -> DB {
+        self.client.clone()
+    }
 
-        let start_key =
-            component_index_path(T::type_path(), &self.field_name, start, &EntityID::Empty);
-        let end_key = component_index_path(T::type_path(), &self.field_name, end, &EntityID::Max);
+    /// 同时满足两个条件的实体，`other` 可以是其他组件上的检索
+    pub fn and<U>(self, other: Filter<U>) -> Self {
+        Self {
+            condition: Condition::And(Box::new(self.condition), Box::new(other.condition)),
+            ..self
+        }
+    }
 
-        let Some(kv) = snapshot
-            .scan(start_key..end_key.into(), 1)
-            .await
-            .map_err(Error::TikvError)?
-            .next()
-        else {
-            return Err(Error::NotFound);
-        };
-        return Ok(EntityID::new_raw(
-            String::from_utf8(kv.value().to_vec())
-                .map_err(|e| Error::InvalidEntityId(e.to_string()))?,
-        ));
+    /// 满足任意一个条件的实体，`other` 可以是其他组件上的检索
+    pub fn or<U>(self, other: Filter<U>) -> Self {
+        Self {
+            condition: Condition::Or(Box::new(self.condition), Box::new(other.condition)),
+            ..self
+        }
     }
 
-    async fn query_entity_id_vec(
-        &self,
-        snapshot: &mut tikv_client::Snapshot,
-    ) -> Result<Vec<EntityID>, Error> {
-        let (start, end) = match &self.bound_condition {
-            BoundCondition::Value(value) => (value, value),
-            BoundCondition::Range(start, end) => (start, end),
-        };
+    /// 不满足条件的实体，单独使用时范围为拥有组件 `T` 的全部实体
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self {
+            condition: Condition::Not(Box::new(self.condition)),
+            ..self
+        }
+    }
 
-        const PAGE_SIZE: usize = 128;
+    /// 最多返回 `limit` 条结果
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
 
-        let mut start_key: Key =
-            component_index_path(T::type_path(), &self.field_name, start, &EntityID::Empty).into();
-        let end_key: Key =
-            component_index_path(T::type_path(), &self.field_name, end, &EntityID::Max).into();
-        let mut entity_ids = Vec::new();
+    /// 跳过前 `offset` 条结果，跳过的结果仍需扫描，深分页时应使用 [`Filter::after`]
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
 
-        loop {
-            let kvs = snapshot
-                .scan(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
-                .await
-                .map_err(Error::TikvError)?
-                .collect::<Vec<_>>();
+    /// 结果的排列顺序，默认为升序
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
 
-            if kvs.is_empty() {
-                break;
-            }
+    /// 从上一页的游标之后继续检索，需要与上一页使用相同的条件和顺序
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.after = Some(cursor);
+        self
+    }
 
-            start_key = next_key(&kvs.last().ok_or(Error::NotFound)?.key().clone());
-            let len = kvs.len();
+    /// 按分页条件计算一页结果，每个实体附带其在结果顺序中的 key，用于生成游标
+    async fn query_page(
+        &self,
+        snapshot: &mut dyn Snapshot,
+        limit: Option<usize>,
+    ) -> Result<Vec<(Key, EntityID)>, Error> {
+        let after = self
+            .after
+            .as_ref()
+            .map(|cursor| Key::from(cursor.0.clone()));
+        self.query_page_from(snapshot, after, self.offset, limit)
+            .await
+    }
 
-            for kv in kvs {
-                entity_ids.push(EntityID::new_raw(
-                    String::from_utf8(kv.value().to_vec())
-                        .map_err(|e| Error::InvalidEntityId(e.to_string()))?,
-                ));
+    /// 从 `after` 之后跳过 `offset` 条，最多返回 `limit` 条结果
+    async fn query_page_from(
+        &self,
+        snapshot: &mut dyn Snapshot,
+        after: Option<Key>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<(Key, EntityID)>, Error> {
+        if let Condition::Index {
+            type_path,
+            field_name,
+            bound_condition,
+        } = &self.condition
+        {
+            let (mut start_key, mut end_key) =
+                bound_condition.key_range(&component_index_field_path(*type_path, field_name));
+            if let Some(after) = after {
+                match self.order {
+                    Order::Asc => start_key = start_key.max(key_after(&after)),
+                    Order::Desc => end_key = end_key.min(after),
+                }
             }
+            return scan_index_page(snapshot, (start_key, end_key), self.order, offset, limit)
+                .await;
+        }
 
-            if len < PAGE_SIZE {
-                break;
-            }
+        let mut entity_ids = sorted(
+            self.condition
+                .query_entity_id_vec(T::type_path(), snapshot)
+                .await?,
+        );
+        if self.order == Order::Desc {
+            entity_ids.reverse();
         }
-        return Ok(entity_ids);
+        Ok(entity_ids
+            .into_iter()
+            .map(|entity_id| (Key::from(format!("{:?}", entity_id)), entity_id))
+            .filter(|(key, _)| {
+                after.as_ref().is_none_or(|after| match self.order {
+                    Order::Asc => key > after,
+                    Order::Desc => key < after,
+                })
+            })
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    async fn query_entity_id(&self, snapshot: &mut dyn Snapshot) -> Result<EntityID, Error> {
+        let limit = self.limit.map_or(1, |limit| limit.min(1));
+        self.query_page(snapshot, Some(limit))
+            .await?
+            .into_iter()
+            .next()
+            .map(|(_, entity_id)| entity_id)
+            .ok_or(Error::NotFound)
+    }
+
+    pub(crate) async fn query_entity_id_vec(
+        &self,
+        snapshot: &mut dyn Snapshot,
+    ) -> Result<Vec<EntityID>, Error> {
+        Ok(self
+            .query_page(snapshot, self.limit)
+            .await?
+            .into_iter()
+            .map(|(_, entity_id)| entity_id)
+            .collect())
+    }
+
+    /// 按 `entity_ids` 的顺序读取组件数据，没有数据的实体会被忽略
+    async fn get_components(
+        &self,
+        snapshot: &mut dyn Snapshot,
+        entity_ids: Vec<EntityID>,
+    ) -> Result<Vec<(EntityID, T)>, Error> {
+        let mut values = snapshot
+            .batch_get(
+                entity_ids
+                    .iter()
+                    .map(|id| component_data_path(T::type_path(), id).into())
+                    .collect::<Vec<_>>(),
+            )
+            .await?
+            .into_iter()
+            .map(|kv| (kv.key().clone(), kv.into_value()))
+            .collect::<HashMap<_, _>>();
+
+        let mut result = Vec::with_capacity(entity_ids.len());
+        for entity_id in entity_ids {
+            let Some(data) = values.remove(&component_data_path(T::type_path(), &entity_id).into())
+            else {
+                continue;
+            };
+            let value = T::decode(data.as_slice()).map_err(Error::DeserializationError)?;
+            result.push((entity_id, value));
+        }
+        Ok(result)
     }
 
     pub async fn entity(&self) -> Result<EntityHandler, Error> {
-        let mut snapshot = self.client.client.snapshot(
-            self.client
-                .client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
-        let entity_id = self.query_entity_id(&mut snapshot).await?;
+        let mut snapshot = self.client.snapshot().await?;
+        let entity_id = self.query_entity_id(&mut *snapshot).await?;
         Ok(EntityHandler {
             entity_id,
-            client: self.client.client.clone(),
+            client: self.client.clone(),
         })
     }
 
     pub async fn single(&self) -> Result<T, Error> {
-        let mut snapshot = self.client.client.snapshot(
-            self.client
-                .client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
-        let entity_id = self.query_entity_id(&mut snapshot).await?;
+        let mut snapshot = self.client.snapshot().await?;
+        let entity_id = self.query_entity_id(&mut *snapshot).await?;
         let Some(data) = snapshot
-            .get(component_data_path(T::type_path(), &entity_id))
-            .await
-            .map_err(Error::TikvError)?
+            .get(component_data_path(T::type_path(), &entity_id).into())
+            .await?
         else {
             return Err(Error::NotFound);
         };
 
         let value = T::decode(data.as_slice()).map_err(Error::DeserializationError)?;
-        return Ok(value);
+        Ok(value)
     }
 
+    /// 满足条件的实体总数，不受分页条件影响
     pub async fn count(&self) -> Result<u64, Error> {
-        let mut snapshot = self.client.client.snapshot(
-            self.client
-                .client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
-        Ok(self.query_entity_id_vec(&mut snapshot).await?.len() as u64)
+        let mut snapshot = self.client.snapshot().await?;
+        Ok(self
+            .condition
+            .query_entity_id_vec(T::type_path(), &mut *snapshot)
+            .await?
+            .len() as u64)
     }
 
     pub async fn all(&self) -> Result<Vec<T>, Error> {
-        let mut snapshot = self.client.client.snapshot(
-            self.client
-                .client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
-        let entity_ids = self.query_entity_id_vec(&mut snapshot).await?;
+        let mut snapshot = self.client.snapshot().await?;
+        let entity_ids = self.query_entity_id_vec(&mut *snapshot).await?;
+        Ok(self
+            .get_components(&mut *snapshot, entity_ids)
+            .await?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect())
+    }
 
-        snapshot
-            .batch_get(
-                entity_ids
-                    .iter()
-                    .map(|id| component_data_path(T::type_path(), id))
-                    .collect::<Vec<_>>(),
+    /// 读取一页结果，设置了 `limit` 且本页已满时返回下一页的游标
+    pub async fn page(&self) -> Result<Page<T>, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        let entity_ids = self.query_page(&mut *snapshot, self.limit).await?;
+        let cursor = match (self.limit, entity_ids.last()) {
+            (Some(limit), Some((key, _))) if entity_ids.len() >= limit => {
+                Some(Cursor(key.clone().into()))
+            }
+            _ => None,
+        };
+        let items = self
+            .get_components(
+                &mut *snapshot,
+                entity_ids.into_iter().map(|(_, id)| id).collect(),
             )
-            .await
-            .map_err(Error::TikvError)?
-            .map(|data| T::decode(data.value().as_slice()).map_err(Error::DeserializationError))
-            .collect()
+            .await?;
+        Ok(Page { items, cursor })
+    }
+
+    /// 逐页读取结果，每页索引只扫描一次并批量读取组件数据，遵循分页条件
+    ///
+    /// 组合条件需要先计算出全部实体 ID，组件数据仍然按页读取
+    #[allow(clippy::type_complexity)]
+    pub fn stream(self) -> Pin<Box<dyn Stream<Item = Result<(EntityID, T), Error>> + Send>>
+    where
+        T: 'static,
+    {
+        const PAGE_SIZE: usize = 128;
+
+        Box::pin(try_stream! {
+            let mut snapshot = self.client.snapshot().await?;
+
+            if !matches!(self.condition, Condition::Index { .. }) {
+                let entity_ids = self.query_entity_id_vec(&mut *snapshot).await?;
+                for chunk in entity_ids.chunks(PAGE_SIZE) {
+                    for item in self.get_components(&mut *snapshot, chunk.to_vec()).await? {
+                        yield item;
+                    }
+                }
+                return;
+            }
+
+            let mut after = self.after.as_ref().map(|cursor| Key::from(cursor.0.clone()));
+            let mut offset = self.offset;
+            let mut remaining = self.limit;
+            loop {
+                let page_size = remaining.map_or(PAGE_SIZE, |remaining| remaining.min(PAGE_SIZE));
+                if page_size == 0 {
+                    break;
+                }
+                let page = self
+                    .query_page_from(&mut *snapshot, after.clone(), offset, Some(page_size))
+                    .await?;
+                let Some((last_key, _)) = page.last() else {
+                    break;
+                };
+                after = Some(last_key.clone());
+                offset = 0;
+                let len = page.len();
+                remaining = remaining.map(|remaining| remaining - len);
+
+                let entity_ids = page.into_iter().map(|(_, id)| id).collect();
+                for item in self.get_components(&mut *snapshot, entity_ids).await? {
+                    yield item;
+                }
+                if len < page_size {
+                    break;
+                }
+            }
+        })
     }
 
     pub async fn list(&self) -> Result<EntityListHandler, Error> {
-        let mut snapshot = self.client.client.snapshot(
-            self.client
-                .client
-                .current_timestamp()
-                .await
-                .map_err(Error::TikvError)?,
-            tikv_client::TransactionOptions::new_optimistic(),
-        );
-        let entity_ids = self.query_entity_id_vec(&mut snapshot).await?;
+        let mut snapshot = self.client.snapshot().await?;
+        let entity_ids = self.query_entity_id_vec(&mut *snapshot).await?;
         Ok(EntityListHandler {
             entity_ids,
-            client: self.client.client.clone(),
+            client: self.client.clone(),
         })
     }
 }
+
+/// 关系索引的检索条件，结果为 `(from, to, T)`，`from` 为调用 `link` 的实体
+pub struct RelationFilter<T> {
+    client: DB,
+    field_name: String,
+    bound_condition: BoundCondition,
+    _marker: PhantomData<T>,
+}
+
+impl<T> RelationFilter<T>
+where
+    T: KvRelation + prost::Message + Default,
+{
+    pub fn new(client: DB, field_name: String, bound_condition: BoundCondition) -> Self {
+        Self {
+            client,
+            field_name,
+            bound_condition,
+            _marker: PhantomData,
+        }
+    }
+
+    async fn query_relation_vec(
+        &self,
+        snapshot: &mut dyn Snapshot,
+        limit: Option<usize>,
+    ) -> Result<Vec<(EntityID, EntityID)>, Error> {
+        const PAGE_SIZE: usize = 128;
+
+        let (mut start_key, end_key) = self
+            .bound_condition
+            .key_range(&relation_index_field_path(T::type_path(), &self.field_name));
+        let mut relations = Vec::new();
+
+        while start_key < end_key {
+            let page_size =
+                limit.map_or(PAGE_SIZE, |limit| (limit - relations.len()).min(PAGE_SIZE));
+            let keys = snapshot
+                .scan_keys(start_key.clone()..end_key.clone(), page_size as u32)
+                .await?;
+
+            if keys.is_empty() {
+                break;
+            }
+
+            start_key = key_after(keys.last().ok_or(Error::NotFound)?);
+            let len = keys.len();
+
+            for key in keys {
+                let key = key_to_string(&key)?;
+                let mut parts = key.rsplit('/');
+                let b = EntityID::new_raw(parts.next().ok_or(Error::NotFound)?.to_string());
+                let a = EntityID::new_raw(parts.next().ok_or(Error::NotFound)?.to_string());
+                relations.push((a, b));
+            }
+
+            if len < page_size || limit.is_some_and(|limit| relations.len() >= limit) {
+                break;
+            }
+        }
+        Ok(relations)
+    }
+
+    async fn get_relations(
+        &self,
+        snapshot: &mut dyn Snapshot,
+        relations: Vec<(EntityID, EntityID)>,
+    ) -> Result<Vec<(EntityID, EntityID, T)>, Error> {
+        let mut values = snapshot
+            .batch_get(
+                relations
+                    .iter()
+                    .map(|(a, b)| relation_data_path(T::type_path(), a, b).into())
+                    .collect::<Vec<_>>(),
+            )
+            .await?
+            .into_iter()
+            .map(|kv| (kv.key().clone(), kv.into_value()))
+            .collect::<HashMap<_, _>>();
+
+        let mut result = Vec::with_capacity(relations.len());
+        for (a, b) in relations {
+            let Some(data) = values.remove(&relation_data_path(T::type_path(), &a, &b).into())
+            else {
+                continue;
+            };
+            let value = T::decode(data.as_slice()).map_err(Error::DeserializationError)?;
+            result.push((a, b, value));
+        }
+        Ok(result)
+    }
+
+    pub async fn single(&self) -> Result<(EntityID, EntityID, T), Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        let relations = self.query_relation_vec(&mut *snapshot, Some(1)).await?;
+        self.get_relations(&mut *snapshot, relations)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(Error::NotFound)
+    }
+
+    pub async fn count(&self) -> Result<u64, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        Ok(self.query_relation_vec(&mut *snapshot, None).await?.len() as u64)
+    }
+
+    pub async fn all(&self) -> Result<Vec<(EntityID, EntityID, T)>, Error> {
+        let mut snapshot = self.client.snapshot().await?;
+        let relations = self.query_relation_vec(&mut *snapshot, None).await?;
+        self.get_relations(&mut *snapshot, relations).await
+    }
+}
@@ -1,25 +1,42 @@
+mod archetype;
+mod backend;
 mod bundle;
+mod chain;
 mod db;
 mod entity_handler;
 mod error;
 mod filter;
 mod meta;
+mod retry;
+mod schema;
+mod transaction_handler;
+mod traverse;
 mod utils;
+mod view;
 
+pub use archetype::{Archetype, ArchetypeQuery, ArchetypeTerm, With, Without};
+pub use backend::{MemoryBackend, Snapshot, StorageBackend, TikvBackend, Transaction};
+pub use chain::Chain;
 pub use db::{DB, EntityID};
-pub use entity_handler::EntityHandler;
+pub use entity_handler::{ConditionalEntityHandler, EntityHandler, EntityListHandler};
 pub use error::Error;
-pub use filter::{BoundCondition, Filter};
+pub use filter::{BoundCondition, Cursor, Filter, Order, Page, RelationFilter};
 pub use kv_entity_derive::{KvComponent, KvRelation};
-pub use utils::{ComponentMeta, RelationDirection};
+pub use retry::RetryPolicy;
+pub use schema::IndexDrift;
+pub use transaction_handler::{TransactionEntityHandler, TransactionHandler};
+pub use traverse::Traversal;
+pub use utils::{Cardinality, ComponentMeta, DeletePolicy, RelationDirection, RelationMeta};
 pub(crate) use utils::{
-    component_data_path, component_index_path, entity_metadata_path, next_key, relation_data_path,
-    relation_edge_no_type_path, relation_edge_path,
+    component_data_path, component_index_path, entity_metadata_path, key_after, next_key,
+    relation_data_path, relation_edge_no_type_path, relation_edge_path,
 };
+pub use view::{ReadView, ReadViewEntity, ReadViewFilter, ReadViewList};
 
 /// KvComponent trait 定义了 KV 存储实体的基本接口
 pub trait KvComponent {
     type Query;
+    type ViewQuery;
 
     /// 返回类型的完整路径，用作 KV 存储的前缀
     fn type_path() -> TypePath;
@@ -27,16 +44,46 @@ pub trait KvComponent {
     /// 返回查询器
     fn query(client: DB) -> Self::Query;
 
+    /// 返回只读视图上的查询器，`client` 为固定了读取时间戳的 [`DB`]
+    fn view_query(client: DB) -> Self::ViewQuery;
+
     /// 返回索引字段和对应的值
     fn indexed_fields(&self) -> Vec<(String, String)>;
 
     /// 返回索引字段名
     fn indexed_field_names() -> Vec<&'static str>;
+
+    /// 返回带有唯一约束（`#[index(unique)]`）的索引字段名
+    fn unique_field_names() -> Vec<&'static str>;
 }
 
 pub trait KvRelation {
+    type Query;
+    type ViewQuery;
+
     /// 返回类型的完整路径，用作 KV 存储的前缀
     fn type_path() -> TypePath;
+
+    /// 返回关系的基数约束
+    fn cardinality() -> Cardinality;
+
+    /// 是否为对称关系，对称关系的两端共用同一条边和同一份数据
+    fn symmetric() -> bool;
+
+    /// 返回删除实体时对该关系另一端的处理方式
+    fn delete_policy() -> DeletePolicy;
+
+    /// 返回查询器
+    fn query(client: DB) -> Self::Query;
+
+    /// 返回只读视图上的查询器，`client` 为固定了读取时间戳的 [`DB`]
+    fn view_query(client: DB) -> Self::ViewQuery;
+
+    /// 返回索引字段和对应的值
+    fn indexed_fields(&self) -> Vec<(String, String)>;
+
+    /// 返回索引字段名
+    fn indexed_field_names() -> Vec<&'static str>;
 }
 
 #[derive(Clone, Copy, Debug)]
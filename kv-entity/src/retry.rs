@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::Error;
+
+/// 乐观事务冲突时的重试策略
+///
+/// 重试会重新执行整个读-改-写过程（包括读取 metadata），
+/// 两次尝试之间按指数退避等待，并可附加随机抖动
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// 最多尝试次数（包括第一次），为 1 时不重试
+    pub max_attempts: u32,
+    /// 第一次重试前的等待时间
+    pub initial_backoff: Duration,
+    /// 单次等待时间的上限
+    pub max_backoff: Duration,
+    /// 是否在等待时间上附加随机抖动
+    pub jitter: bool,
+    /// 判断错误是否可以重试
+    pub retryable: fn(&Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            jitter: true,
+            retryable: Error::is_retryable,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不进行重试
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn retryable(mut self, retryable: fn(&Error) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    pub(crate) fn should_retry(&self, error: &Error, attempt: u32) -> bool {
+        attempt < self.max_attempts && (self.retryable)(error)
+    }
+
+    /// 第 `attempt` 次尝试失败后的等待时间
+    pub(crate) fn backoff_for(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << exp)
+            .min(self.max_backoff);
+        if self.jitter && !backoff.is_zero() {
+            rand::thread_rng().gen_range(backoff / 2..=backoff)
+        } else {
+            backoff
+        }
+    }
+}
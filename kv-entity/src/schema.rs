@@ -0,0 +1,284 @@
+use std::collections::{BTreeSet, HashMap};
+
+use prost::Message;
+use tikv_client::Key;
+
+use crate::{
+    ComponentMeta, DB, Error, TypePath, component_data_path,
+    db::EntityID,
+    key_after,
+    meta::ComponentSchema,
+    next_key,
+    utils::{
+        component_index_field_path, component_schema_path, component_unique_field_path,
+        key_to_string,
+    },
+};
+
+/// 组件索引字段的变化
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexDrift {
+    pub type_path: &'static str,
+    /// 新增了 `#[index]` 的字段
+    pub added: Vec<String>,
+    /// 移除了 `#[index]` 的字段
+    pub removed: Vec<String>,
+    /// 新增了唯一约束的字段
+    pub unique_added: Vec<String>,
+    /// 移除了唯一约束的字段
+    pub unique_removed: Vec<String>,
+}
+
+impl DB {
+    /// 比对持久化的索引记录和当前编译的组件定义，返回索引字段发生变化的组件
+    pub async fn index_drift(&self) -> Result<Vec<IndexDrift>, Error> {
+        let mut snapshot = self.snapshot().await?;
+        let mut drifts = Vec::new();
+        for meta in inventory::iter::<ComponentMeta>() {
+            let compiled = (meta.indexed_field_names)()
+                .into_iter()
+                .map(str::to_string)
+                .collect::<BTreeSet<_>>();
+            let compiled_unique = (meta.unique_field_names)()
+                .into_iter()
+                .map(str::to_string)
+                .collect::<BTreeSet<_>>();
+            let stored = match snapshot
+                .get(component_schema_path(TypePath(meta.type_path)).into())
+                .await?
+            {
+                Some(data) => {
+                    ComponentSchema::decode(data.as_slice()).map_err(Error::DeserializationError)?
+                }
+                None => ComponentSchema::default(),
+            };
+            let stored_unique = stored.unique_fields.into_iter().collect::<BTreeSet<_>>();
+            let stored = stored.indexed_fields.into_iter().collect::<BTreeSet<_>>();
+            if compiled == stored && compiled_unique == stored_unique {
+                continue;
+            }
+            drifts.push(IndexDrift {
+                type_path: meta.type_path,
+                added: compiled.difference(&stored).cloned().collect(),
+                removed: stored.difference(&compiled).cloned().collect(),
+                unique_added: compiled_unique
+                    .difference(&stored_unique)
+                    .cloned()
+                    .collect(),
+                unique_removed: stored_unique
+                    .difference(&compiled_unique)
+                    .cloned()
+                    .collect(),
+            });
+        }
+        Ok(drifts)
+    }
+
+    /// 检测索引变化，并为发生变化的组件重建索引
+    ///
+    /// 对每个拥有该组件的实体，按当前的 `#[index]` 字段重新写入索引 key、删除已移除字段的索引 key，
+    /// 并更新 metadata 中的 `index_keys`，完成后记录新的索引字段。返回重建过的组件
+    ///
+    /// 新增唯一约束时，若已有数据存在重复值，返回 [`Error::UniqueViolation`]，索引记录保持不变
+    ///
+    /// 重建按页分多个事务提交，每页在写入索引的同一个事务中通过 `get_for_update` 锁定并检查唯一 key，
+    /// 重建期间的并发写入引入重复值时同样返回 [`Error::UniqueViolation`]，此时已提交的页会保留，
+    /// 但索引记录不变，下次调用会重新检测到变化并重建。绕过索引直接写入组件数据的旧版本写入方
+    /// 不会被检测到，需要在重建前停止
+    pub async fn sync_indexes(&self) -> Result<Vec<IndexDrift>, Error> {
+        let drifts = self.index_drift().await?;
+        for drift in drifts.iter() {
+            let Some(meta) =
+                inventory::iter::<ComponentMeta>().find(|meta| meta.type_path == drift.type_path)
+            else {
+                continue;
+            };
+            log::info!(
+                "rebuild index of {}, added: {:?}, removed: {:?}, unique added: {:?}, unique removed: {:?}",
+                drift.type_path,
+                drift.added,
+                drift.removed,
+                drift.unique_added,
+                drift.unique_removed
+            );
+            self.rebuild_component_index(meta, drift).await?;
+        }
+        Ok(drifts)
+    }
+
+    /// 在后台任务中执行 [`DB::sync_indexes`]
+    pub fn sync_indexes_in_background(
+        &self,
+    ) -> tokio::task::JoinHandle<Result<Vec<IndexDrift>, Error>> {
+        let db = self.clone();
+        tokio::spawn(async move { db.sync_indexes().await })
+    }
+
+    async fn rebuild_component_index(
+        &self,
+        meta: &'static ComponentMeta,
+        drift: &IndexDrift,
+    ) -> Result<(), Error> {
+        const PAGE_SIZE: usize = 128;
+        let type_path = TypePath(meta.type_path);
+
+        // 重建按页分多个事务提交，先整体校验唯一约束，避免已有的重复值留下写了一半的索引，
+        // 校验之后的并发写入由每页事务中 `reindex_component` 对唯一 key 的加锁检查发现
+        self.check_unique_values(meta).await?;
+
+        let mut start_key: Key = component_data_path(type_path, &EntityID::Empty).into();
+        let end_key: Key = component_data_path(type_path, &EntityID::Max).into();
+        loop {
+            let keys = self
+                .snapshot()
+                .await?
+                .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
+                .await?;
+            if keys.is_empty() {
+                break;
+            }
+            start_key = key_after(keys.last().ok_or(Error::NotFound)?);
+            let len = keys.len();
+
+            let keys = &keys;
+            self.run_optimistic(|txn| async move {
+                let mut txn = txn.lock().await;
+                let mut mutations = Vec::new();
+                for kv in txn.batch_get(keys.clone()).await? {
+                    let key = key_to_string(kv.key())?;
+                    let entity = self.entity(EntityID::new_raw(
+                        key.split('/').nth(3).ok_or(Error::NotFound)?.to_string(),
+                    ));
+                    let mut metadata = entity.get_metadata(&mut **txn).await?.unwrap_or_default();
+                    entity
+                        .reindex_component(
+                            &mut **txn,
+                            type_path,
+                            (meta.indexed_values)(kv.value())?,
+                            &(meta.unique_field_names)(),
+                            &mut mutations,
+                            &mut metadata,
+                        )
+                        .await?;
+                    entity.put_metadata(&mut **txn, &metadata).await?;
+                }
+                txn.batch_mutate(mutations).await
+            })
+            .await?;
+
+            if len < PAGE_SIZE {
+                break;
+            }
+        }
+
+        // 清理已移除字段上残留的索引 key
+        let removed_prefixes = drift
+            .removed
+            .iter()
+            .map(|field| component_index_field_path(type_path, field))
+            .chain(
+                drift
+                    .unique_removed
+                    .iter()
+                    .map(|field| component_unique_field_path(type_path, field)),
+            );
+        for prefix in removed_prefixes {
+            let start_key: Key = prefix.into();
+            let end_key = next_key(&start_key);
+            loop {
+                let start_key = &start_key;
+                let end_key = &end_key;
+                let len = self
+                    .run_optimistic(|txn| async move {
+                        let mut txn = txn.lock().await;
+                        let keys = txn
+                            .scan_keys(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
+                            .await?;
+                        let len = keys.len();
+                        for key in keys {
+                            txn.delete(key).await?;
+                        }
+                        Ok(len)
+                    })
+                    .await?;
+                if len < PAGE_SIZE {
+                    break;
+                }
+            }
+        }
+
+        let schema = ComponentSchema {
+            indexed_fields: (meta.indexed_field_names)()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            unique_fields: (meta.unique_field_names)()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        };
+        let schema = &schema;
+        self.run_optimistic(|txn| async move {
+            txn.lock()
+                .await
+                .put(
+                    component_schema_path(type_path).into(),
+                    schema.encode_to_vec(),
+                )
+                .await
+        })
+        .await
+    }
+
+    /// 扫描组件的全部数据，检查唯一字段是否存在重复值
+    async fn check_unique_values(&self, meta: &'static ComponentMeta) -> Result<(), Error> {
+        const PAGE_SIZE: usize = 128;
+        let unique_fields = (meta.unique_field_names)();
+        if unique_fields.is_empty() {
+            return Ok(());
+        }
+        let type_path = TypePath(meta.type_path);
+        let mut snapshot = self.snapshot().await?;
+
+        let mut owners: HashMap<(String, String), EntityID> = HashMap::new();
+        let mut start_key: Key = component_data_path(type_path, &EntityID::Empty).into();
+        let end_key: Key = component_data_path(type_path, &EntityID::Max).into();
+        loop {
+            let kvs = snapshot
+                .scan(start_key.clone()..end_key.clone(), PAGE_SIZE as u32)
+                .await?;
+            if kvs.is_empty() {
+                break;
+            }
+            start_key = key_after(kvs.last().ok_or(Error::NotFound)?.key());
+            let len = kvs.len();
+            for kv in kvs {
+                let key = key_to_string(kv.key())?;
+                let entity_id =
+                    EntityID::new_raw(key.split('/').nth(3).ok_or(Error::NotFound)?.to_string());
+                for (field, value) in (meta.indexed_values)(kv.value())? {
+                    if !unique_fields.contains(&field.as_str()) {
+                        continue;
+                    }
+                    match owners.entry((field, value)) {
+                        std::collections::hash_map::Entry::Occupied(entry) => {
+                            let (field, value) = entry.key().clone();
+                            return Err(Error::UniqueViolation {
+                                field,
+                                value,
+                                existing_entity: entry.get().clone(),
+                            });
+                        }
+                        std::collections::hash_map::Entry::Vacant(entry) => {
+                            entry.insert(entity_id.clone());
+                        }
+                    }
+                }
+            }
+            if len < PAGE_SIZE {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
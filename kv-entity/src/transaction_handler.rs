@@ -0,0 +1,194 @@
+use prost::Message;
+
+use crate::{
+    DB, Error, KvComponent, KvRelation, RelationDirection,
+    backend::SharedTransaction,
+    bundle::{AttachMode, ComponentBundle, ComponentSet},
+    db::EntityID,
+    entity_handler::EntityHandler,
+};
+
+/// 用户控制的事务，通过 [`DB::transaction`] 获得
+///
+/// 其上的所有操作在同一个事务中执行，闭包返回 `Ok` 时统一提交，返回 `Err` 时回滚
+#[derive(Clone)]
+pub struct TransactionHandler {
+    pub(crate) txn: SharedTransaction,
+    pub(crate) client: DB,
+}
+
+impl TransactionHandler {
+    pub fn entity(&self, entity_id: impl Into<EntityID>) -> TransactionEntityHandler {
+        TransactionEntityHandler {
+            entity: self.client.entity(entity_id),
+            txn: self.txn.clone(),
+        }
+    }
+}
+
+/// 事务中的单个实体，提供与 [`EntityHandler`] 相同的读写操作
+#[derive(Clone)]
+pub struct TransactionEntityHandler {
+    entity: EntityHandler,
+    txn: SharedTransaction,
+}
+
+impl TransactionEntityHandler {
+    pub fn entity_id(&self) -> &EntityID {
+        self.entity.entity_id()
+    }
+
+    /// 读取组件，能看到本事务中尚未提交的修改
+    pub async fn get<T: KvComponent + Message + Default>(&self) -> Result<Option<T>, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity.get_in_txn(&mut **txn).await
+    }
+
+    /// 实体的版本号，能看到本事务中尚未提交的修改
+    pub async fn revision(&self) -> Result<u64, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity.revision_in_txn(&mut **txn).await
+    }
+
+    /// 通过一次 `batch_get` 读取多个组件，能看到本事务中尚未提交的修改
+    pub async fn get_many<S: ComponentSet>(&self) -> Result<S::Output, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity.get_many_in_txn::<S>(&mut **txn).await
+    }
+
+    pub async fn attach(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .attach_in_txn(&mut **txn, bundle, AttachMode::Upsert)
+            .await?;
+        Ok(self.clone())
+    }
+
+    /// 写入组件，`bundle` 中任意组件已存在时返回 [`Error::AlreadyExists`]
+    pub async fn insert(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .attach_in_txn(&mut **txn, bundle, AttachMode::Insert)
+            .await?;
+        Ok(self.clone())
+    }
+
+    /// 覆盖组件，`bundle` 中任意组件不存在时返回 [`Error::NotFound`]
+    pub async fn replace(&self, bundle: impl ComponentBundle) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .attach_in_txn(&mut **txn, bundle, AttachMode::Replace)
+            .await?;
+        Ok(self.clone())
+    }
+
+    /// 读取组件、调用 `f` 修改后写回，返回修改后的值，组件不存在时返回 [`Error::NotFound`]
+    pub async fn update<T>(&self, f: impl FnOnce(&mut T)) -> Result<T, Error>
+    where
+        T: KvComponent + Message + Default + Clone,
+    {
+        let mut txn = self.txn.lock().await;
+        self.entity.update_in_txn(&mut **txn, || None, f).await
+    }
+
+    /// 同 [`TransactionEntityHandler::update`]，组件不存在时以 `default` 的返回值为初始值
+    pub async fn upsert_with<T>(
+        &self,
+        default: impl FnOnce() -> T,
+        f: impl FnOnce(&mut T),
+    ) -> Result<T, Error>
+    where
+        T: KvComponent + Message + Default + Clone,
+    {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .update_in_txn(&mut **txn, || Some(default()), f)
+            .await
+    }
+
+    pub async fn detach<T: KvComponent + Message + Default>(&self) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity.detach_in_txn::<T>(&mut **txn).await?;
+        Ok(self.clone())
+    }
+
+    pub async fn delete(&self) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        let mut mutations = Vec::new();
+        self.entity
+            .delete_in_txn(&mut **txn, &mut mutations)
+            .await?;
+        txn.batch_mutate(mutations).await?;
+        Ok(self.clone())
+    }
+
+    /// 读取本实体通过 `link` 关联到 `entity_id` 的关系数据，能看到本事务中尚未提交的修改
+    pub async fn relation<T: KvRelation + Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<Option<T>, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .relation_in_txn(&mut **txn, &entity_id.into())
+            .await
+    }
+
+    /// 本实体 `direction` 方向上 `T` 关系的数量，能看到本事务中尚未提交的修改
+    pub async fn degree<T: KvRelation + Message + Default>(
+        &self,
+        direction: RelationDirection,
+    ) -> Result<u64, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .degree_in_txn(T::type_path(), direction, &mut **txn)
+            .await
+    }
+
+    /// 读取关系数据、调用 `f` 修改后写回，关系不存在时返回 [`Error::NotFound`]
+    pub async fn update_relation<T: KvRelation + Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        f: impl FnOnce(&mut T),
+    ) -> Result<T, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .update_relation_in_txn(&mut **txn, &entity_id.into(), f)
+            .await
+    }
+
+    pub async fn link<T: KvRelation + Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        value: T,
+    ) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .link_in_txn(&mut **txn, &entity_id.into(), &value, false)
+            .await?;
+        Ok(self.clone())
+    }
+
+    /// 与 [`EntityHandler::link_replace`] 相同，在本事务中解除受基数约束的已有关系
+    pub async fn link_replace<T: KvRelation + Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+        value: T,
+    ) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .link_in_txn(&mut **txn, &entity_id.into(), &value, true)
+            .await?;
+        Ok(self.clone())
+    }
+
+    pub async fn unlink<T: KvRelation + Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<Self, Error> {
+        let mut txn = self.txn.lock().await;
+        self.entity
+            .unlink_in_txn::<T>(&mut **txn, &entity_id.into())
+            .await?;
+        Ok(self.clone())
+    }
+}
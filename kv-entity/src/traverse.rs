@@ -0,0 +1,213 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::{Bound, RangeBounds},
+    pin::Pin,
+};
+
+use async_stream::try_stream;
+use futures::{Stream, StreamExt, TryStreamExt};
+
+use crate::{
+    DB, Error, KvRelation, RelationDirection, TypePath, db::EntityID, entity_handler::EntityHandler,
+};
+
+/// 单层展开的实体数量上限的默认值
+const DEFAULT_MAX_FRONTIER: usize = 10_000;
+
+/// 展开一层时同时进行的边扫描数量
+const SCAN_CONCURRENCY: usize = 16;
+
+/// 沿关系的广度优先遍历，通过 [`EntityHandler::traverse`] 构建
+///
+/// 每个实体只会被访问一次，因此环不会导致重复结果，所有层在同一个快照上读取
+pub struct Traversal {
+    client: DB,
+    start: EntityID,
+    type_path: TypePath,
+    direction: RelationDirection,
+    depth: (Bound<usize>, Bound<usize>),
+    max_frontier: usize,
+}
+
+impl Traversal {
+    /// 返回的深度范围，起点的深度为 0，默认只返回直接相邻的实体
+    ///
+    /// 范围为空时（如 `0..0`）遍历返回 [`Error::InvalidDepth`]
+    pub fn depth(mut self, depth: impl RangeBounds<usize>) -> Self {
+        self.depth = (depth.start_bound().cloned(), depth.end_bound().cloned());
+        self
+    }
+
+    /// 深度范围的上下界，均为闭区间
+    fn depth_range(&self) -> Result<(usize, usize), Error> {
+        let min_depth = match self.depth.0 {
+            Bound::Included(depth) => Some(depth),
+            Bound::Excluded(depth) => depth.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let max_depth = match self.depth.1 {
+            Bound::Included(depth) => Some(depth),
+            Bound::Excluded(depth) => depth.checked_sub(1),
+            Bound::Unbounded => Some(usize::MAX),
+        };
+        match (min_depth, max_depth) {
+            (Some(min_depth), Some(max_depth)) if min_depth <= max_depth => {
+                Ok((min_depth, max_depth))
+            }
+            _ => Err(Error::InvalidDepth(format!("{:?}", self.depth))),
+        }
+    }
+
+    /// 单层最多展开的实体数量，超过时返回 [`Error::TraversalLimitExceeded`]
+    pub fn max_frontier(mut self, max_frontier: usize) -> Self {
+        self.max_frontier = max_frontier;
+        self
+    }
+
+    /// 按深度从小到大返回可以到达的实体及其深度
+    #[allow(clippy::type_complexity)]
+    pub fn stream(self) -> Pin<Box<dyn Stream<Item = Result<(EntityID, usize), Error>> + Send>> {
+        Box::pin(try_stream! {
+            let (min_depth, max_depth) = self.depth_range()?;
+            let client = self.pinned_client().await?;
+            let mut visited = HashSet::from([self.start.clone()]);
+            let mut frontier = vec![self.start.clone()];
+            let mut depth = 0;
+
+            loop {
+                if depth >= min_depth {
+                    for entity_id in frontier.iter() {
+                        yield (entity_id.clone(), depth);
+                    }
+                }
+                if depth >= max_depth {
+                    break;
+                }
+                let next = self.expand(&client, &frontier, &mut visited).await?;
+                if next.is_empty() {
+                    break;
+                }
+                frontier = next.into_iter().map(|(entity_id, _)| entity_id).collect();
+                depth += 1;
+            }
+        })
+    }
+
+    /// 到 `target` 的最短路径，包含起点和终点，超过最大深度仍未到达时返回 None
+    pub async fn path_to(
+        &self,
+        target: impl Into<EntityID>,
+    ) -> Result<Option<Vec<EntityID>>, Error> {
+        let target = target.into();
+        let (_, max_depth) = self.depth_range()?;
+        let client = self.pinned_client().await?;
+        let mut visited = HashSet::from([self.start.clone()]);
+        let mut parents = HashMap::new();
+        let mut frontier = vec![self.start.clone()];
+        let mut depth = 0;
+
+        while frontier.iter().all(|entity_id| *entity_id != target) {
+            if depth >= max_depth {
+                return Ok(None);
+            }
+            let next = self.expand(&client, &frontier, &mut visited).await?;
+            if next.is_empty() {
+                return Ok(None);
+            }
+            frontier = Vec::with_capacity(next.len());
+            for (entity_id, parent) in next {
+                parents.insert(entity_id.clone(), parent);
+                frontier.push(entity_id);
+            }
+            depth += 1;
+        }
+
+        let mut path = vec![target];
+        while let Some(parent) = parents.get(path.last().ok_or(Error::NotFound)?) {
+            path.push(parent.clone());
+        }
+        path.reverse();
+        Ok(Some(path))
+    }
+
+    /// 固定读取时间戳的 [`DB`]，使所有层的扫描读到同一个快照
+    async fn pinned_client(&self) -> Result<DB, Error> {
+        let timestamp = match &self.client.read_timestamp {
+            Some(timestamp) => timestamp.clone(),
+            None => self.client.backend.current_timestamp().await?,
+        };
+        Ok(DB {
+            read_timestamp: Some(timestamp),
+            ..self.client.clone()
+        })
+    }
+
+    /// 批量扫描 `frontier` 中实体的边，返回未访问过的相邻实体和它的上一跳
+    ///
+    /// 同一层的扫描并发进行，结果仍按 `frontier` 的顺序合并，保证遍历顺序稳定
+    async fn expand(
+        &self,
+        client: &DB,
+        frontier: &[EntityID],
+        visited: &mut HashSet<EntityID>,
+    ) -> Result<Vec<(EntityID, EntityID)>, Error> {
+        let (type_path, direction) = (self.type_path, self.direction);
+        let mut scans = futures::stream::iter(frontier.to_vec())
+            .map(|entity_id| {
+                let client = client.clone();
+                async move {
+                    let mut snapshot = client.snapshot().await?;
+                    let edges = client
+                        .entity(entity_id.clone())
+                        .edges_entity_in_txn(type_path, direction, &mut *snapshot)
+                        .await?;
+                    Ok::<_, Error>((entity_id, edges))
+                }
+            })
+            .buffered(SCAN_CONCURRENCY);
+
+        let mut next = Vec::new();
+        while let Some((entity_id, edges)) = scans.try_next().await? {
+            for (neighbor, _) in edges {
+                if !visited.insert(neighbor.clone()) {
+                    continue;
+                }
+                if next.len() >= self.max_frontier {
+                    return Err(Error::TraversalLimitExceeded(self.max_frontier));
+                }
+                next.push((neighbor, entity_id.clone()));
+            }
+        }
+        Ok(next)
+    }
+}
+
+impl EntityHandler {
+    /// 从当前实体出发沿 `R` 关系做广度优先遍历，`direction` 的含义与 [`EntityHandler::edges`] 相同
+    pub fn traverse<R: KvRelation>(&self, direction: RelationDirection) -> Traversal {
+        Traversal {
+            client: self.client.clone(),
+            start: self.entity_id.clone(),
+            type_path: R::type_path(),
+            direction,
+            depth: (Bound::Included(1), Bound::Included(1)),
+            max_frontier: DEFAULT_MAX_FRONTIER,
+        }
+    }
+}
+
+impl DB {
+    /// `a` 到 `b` 不超过 `max_depth` 跳的最短路径，不区分 `R` 关系的方向
+    pub async fn shortest_path<R: KvRelation>(
+        &self,
+        a: impl Into<EntityID>,
+        b: impl Into<EntityID>,
+        max_depth: usize,
+    ) -> Result<Option<Vec<EntityID>>, Error> {
+        self.entity(a)
+            .traverse::<R>(RelationDirection::Both)
+            .depth(..=max_depth)
+            .path_to(b)
+            .await
+    }
+}
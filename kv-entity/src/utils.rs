@@ -15,6 +15,13 @@ pub(crate) fn next_key(key: &Key) -> Key {
     Key::from(next_key)
 }
 
+/// 紧跟在 `key` 之后的 key，用于分页扫描时从上一页的最后一个 key 继续
+pub(crate) fn key_after(key: &Key) -> Key {
+    let mut key_after = Into::<Vec<u8>>::into(key.clone());
+    key_after.push(0);
+    Key::from(key_after)
+}
+
 pub(crate) fn component_data_path(type_path: TypePath, entity_id: &EntityID) -> String {
     format!("component/single/{}/{:?}", type_path.0, entity_id)
 }
@@ -23,6 +30,13 @@ pub(crate) fn entity_metadata_path(entity_id: &EntityID) -> String {
     format!("entity/metadata/{:?}", entity_id)
 }
 
+/// 实体删除时留下的版本号下限，值为十进制字符串，重新创建的实体从这里继续递增
+///
+/// 重新创建实体时读取后立即删除，只有删除后没有再创建的实体才会保留这个 key
+pub(crate) fn entity_revision_path(entity_id: &EntityID) -> String {
+    format!("entity/revision/{:?}", entity_id)
+}
+
 pub(crate) fn component_index_path(
     type_path: TypePath,
     field_name: &str,
@@ -35,6 +49,24 @@ pub(crate) fn component_index_path(
     )
 }
 
+/// 唯一索引 key，值为拥有该值的实体
+///
+/// 字段名后的 `.unique` 不会出现在 Rust 标识符中，保证它不会落在普通索引的扫描范围内
+pub(crate) fn component_unique_path(type_path: TypePath, field_name: &str, value: &str) -> String {
+    format!(
+        "component/index/{}/{}.unique/{}",
+        type_path.0, field_name, value
+    )
+}
+
+pub(crate) fn component_unique_field_path(type_path: TypePath, field_name: &str) -> String {
+    format!("component/index/{}/{}.unique/", type_path.0, field_name)
+}
+
+pub(crate) fn component_index_field_path(type_path: TypePath, field_name: &str) -> String {
+    format!("component/index/{}/{}/", type_path.0, field_name)
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum RelationDirection {
     Both,
@@ -42,6 +74,44 @@ pub enum RelationDirection {
     Out,
 }
 
+/// 关系的基数约束，`from` 为调用 `link` 的实体，`to` 为被关联的实体
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cardinality {
+    /// 每个 `from` 最多关联一个 `to`，每个 `to` 最多被一个 `from` 关联
+    OneToOne,
+    /// 每个 `to` 最多被一个 `from` 关联
+    OneToMany,
+    /// 每个 `from` 最多关联一个 `to`
+    ManyToOne,
+    /// 不做限制
+    #[default]
+    ManyToMany,
+}
+
+impl Cardinality {
+    /// `from` 是否只能有一条该类型的关系
+    pub(crate) fn limits_from(self) -> bool {
+        matches!(self, Cardinality::OneToOne | Cardinality::ManyToOne)
+    }
+
+    /// `to` 是否只能有一条该类型的关系
+    pub(crate) fn limits_to(self) -> bool {
+        matches!(self, Cardinality::OneToOne | Cardinality::OneToMany)
+    }
+}
+
+/// 删除实体时对其关系另一端的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeletePolicy {
+    /// 只删除关系，保留另一端的实体
+    #[default]
+    Detach,
+    /// 同时删除本实体通过 `link` 关联到的实体，被删除的实体继续按各自关系的策略处理
+    Cascade,
+    /// 仍有其他实体通过 `link` 关联到本实体时拒绝删除
+    Restrict,
+}
+
 impl std::ops::Not for RelationDirection {
     type Output = RelationDirection;
     fn not(self) -> Self::Output {
@@ -73,6 +143,81 @@ pub(crate) fn relation_data_path(type_path: TypePath, a: &EntityID, b: &EntityID
     format!("relation/data/{}/{:?}/{:?}", type_path.0, a, b)
 }
 
+/// 从 `entity` 一端按 `direction` 看到的关系在存储中的位置，
+/// 返回数据 key 中的 `(a, b)` 以及 `entity` 和 `other` 两端边的方向
+///
+/// 对称关系不区分方向，`(a, b)` 按实体 ID 排序，两端的边都记为 `in`
+pub(crate) fn relation_layout(
+    symmetric: bool,
+    entity: &EntityID,
+    other: &EntityID,
+    direction: RelationDirection,
+) -> ((EntityID, EntityID), RelationDirection, RelationDirection) {
+    if symmetric {
+        let pair = if entity <= other {
+            (entity.clone(), other.clone())
+        } else {
+            (other.clone(), entity.clone())
+        };
+        return (pair, RelationDirection::In, RelationDirection::In);
+    }
+    match direction {
+        RelationDirection::Out => (
+            (other.clone(), entity.clone()),
+            RelationDirection::Out,
+            RelationDirection::In,
+        ),
+        _ => (
+            (entity.clone(), other.clone()),
+            RelationDirection::In,
+            RelationDirection::Out,
+        ),
+    }
+}
+
+/// 受基数约束的一端唯一的关系，值为另一端的实体
+pub(crate) fn relation_single_path(
+    type_path: TypePath,
+    entity_id: &EntityID,
+    direction: RelationDirection,
+) -> String {
+    let io = match direction {
+        RelationDirection::Out => "out",
+        _ => "in",
+    };
+    format!("relation/single/{:?}/{}/{}", entity_id, type_path.0, io)
+}
+
+/// 实体在一个方向上该类型关系的数量
+pub(crate) fn relation_degree_path(
+    type_path: TypePath,
+    entity_id: &EntityID,
+    direction: RelationDirection,
+) -> String {
+    let io = match direction {
+        RelationDirection::Out => "out",
+        _ => "in",
+    };
+    format!("relation/degree/{:?}/{}/{}", entity_id, type_path.0, io)
+}
+
+pub(crate) fn relation_index_path(
+    type_path: TypePath,
+    field_name: &str,
+    value: &str,
+    a: &EntityID,
+    b: &EntityID,
+) -> String {
+    format!(
+        "relation/index/{}/{}/{}/{:?}/{:?}",
+        type_path.0, field_name, value, a, b
+    )
+}
+
+pub(crate) fn relation_index_field_path(type_path: TypePath, field_name: &str) -> String {
+    format!("relation/index/{}/{}/", type_path.0, field_name)
+}
+
 pub(crate) fn relation_edge_no_type_path(entity_id: &EntityID, type_path: TypePath) -> String {
     format!("relation/edge/{:?}/{}", entity_id, type_path.0)
 }
@@ -81,10 +226,19 @@ pub(crate) fn component_increment_id_path(type_path: TypePath) -> String {
     format!("component/increment_id/{}", type_path.0)
 }
 
+pub(crate) fn component_schema_path(type_path: TypePath) -> String {
+    format!("schema/component/{}", type_path.0)
+}
+
+/// 从编码后的组件数据中计算索引字段和对应的值
+pub type IndexedValuesFn = fn(&[u8]) -> Result<Vec<(String, String)>, Error>;
+
 // 定义组件元信息
 pub struct ComponentMeta {
     pub type_path: &'static str,
     pub indexed_field_names: fn() -> Vec<&'static str>,
+    pub unique_field_names: fn() -> Vec<&'static str>,
+    pub indexed_values: IndexedValuesFn,
 }
 
 impl std::fmt::Debug for ComponentMeta {
@@ -101,6 +255,47 @@ impl std::fmt::Debug for ComponentMeta {
 // 使用 inventory 收集所有组件
 inventory::collect!(ComponentMeta);
 
+// 定义关系元信息
+pub struct RelationMeta {
+    pub type_path: &'static str,
+    pub cardinality: Cardinality,
+    pub symmetric: bool,
+    pub delete_policy: DeletePolicy,
+    pub indexed_field_names: fn() -> Vec<&'static str>,
+    pub indexed_values: IndexedValuesFn,
+}
+
+impl std::fmt::Debug for RelationMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RelationMeta {{ type_path: {}, indexed: {:?} }}",
+            self.type_path,
+            (self.indexed_field_names)()
+        )
+    }
+}
+
+// 使用 inventory 收集所有关系
+inventory::collect!(RelationMeta);
+
+pub(crate) fn relation_meta(type_path: &str) -> Option<&'static RelationMeta> {
+    inventory::iter::<RelationMeta>().find(|meta| meta.type_path == type_path)
+}
+
+/// 未注册的关系类型无法确定基数，按一对一处理，保证删除关系时不会残留单边记录
+pub(crate) fn relation_cardinality(type_path: TypePath) -> Cardinality {
+    relation_meta(type_path.0).map_or(Cardinality::OneToOne, |meta| meta.cardinality)
+}
+
+pub(crate) fn relation_symmetric(type_path: TypePath) -> bool {
+    relation_meta(type_path.0).is_some_and(|meta| meta.symmetric)
+}
+
+pub(crate) fn relation_delete_policy(type_path: TypePath) -> DeletePolicy {
+    relation_meta(type_path.0).map_or(DeletePolicy::Detach, |meta| meta.delete_policy)
+}
+
 // 获取所有已注册的组件
 #[allow(unused)]
 pub fn all_components() -> std::collections::HashMap<&'static str, Vec<&'static str>> {
@@ -110,7 +305,7 @@ pub fn all_components() -> std::collections::HashMap<&'static str, Vec<&'static
 }
 
 pub(crate) fn key_to_string(key: &Key) -> Result<String, Error> {
-    Ok(String::from_utf8(Into::<Vec<u8>>::into(key.clone())).map_err(Error::InvalidUtf8)?)
+    String::from_utf8(Into::<Vec<u8>>::into(key.clone())).map_err(Error::InvalidUtf8)
 }
 
 // 全局字符串缓存池
@@ -0,0 +1,270 @@
+use std::pin::Pin;
+
+use futures::Stream;
+use tikv_client::Timestamp;
+
+use crate::{
+    DB, Error, KvComponent, KvRelation, RelationDirection,
+    archetype::{Archetype, ArchetypeQuery},
+    bundle::ComponentSet,
+    db::EntityID,
+    entity_handler::{EntityHandler, EntityListHandler},
+    filter::{BoundCondition, Cursor, Filter, Order, Page},
+    meta::EntityMetadata,
+};
+
+/// 固定时间戳上的只读视图，通过 [`DB::at`]、[`DB::at_time`] 或 [`DB::read_session`] 获得
+///
+/// 视图上的所有读取都看到该时刻已提交的数据，多次读取之间不会观察到中间状态
+#[derive(Clone)]
+pub struct ReadView {
+    client: DB,
+    timestamp: Timestamp,
+}
+
+impl ReadView {
+    pub(crate) fn new(client: DB, timestamp: Timestamp) -> Self {
+        Self {
+            client: DB {
+                read_timestamp: Some(timestamp.clone()),
+                ..client
+            },
+            timestamp,
+        }
+    }
+
+    pub fn timestamp(&self) -> &Timestamp {
+        &self.timestamp
+    }
+
+    pub fn entity(&self, entity_id: impl Into<EntityID>) -> ReadViewEntity {
+        ReadViewEntity {
+            entity: self.client.entity(entity_id),
+        }
+    }
+
+    /// 组件检索，检索方法返回 [`ReadViewFilter`]
+    pub fn query<T: KvComponent + prost::Message + Default>(&self) -> T::ViewQuery {
+        T::view_query(self.client.clone())
+    }
+
+    pub fn query_relation<T: KvRelation + prost::Message + Default>(&self) -> T::ViewQuery {
+        T::view_query(self.client.clone())
+    }
+
+    pub fn query_archetype<A>(&self) -> ArchetypeQuery<A>
+    where
+        A: Archetype + 'static,
+        A::Item: Send,
+    {
+        self.client.query_archetype::<A>()
+    }
+
+    /// 拥有组件 `T` 的全部实体
+    pub async fn get_entity<T: KvComponent + prost::Message + Default + 'static>(
+        &self,
+    ) -> Result<ReadViewList, Error> {
+        Ok(ReadViewList {
+            list: self.client.get_entity::<T>().await?,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn get<T: KvComponent + prost::Message + Default + 'static>(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = Result<(EntityID, T), Error>> + '_>> {
+        self.client.get::<T>()
+    }
+}
+
+/// 只读视图中的单个实体，提供 [`EntityHandler`] 的读取操作
+#[derive(Clone)]
+pub struct ReadViewEntity {
+    entity: EntityHandler,
+}
+
+impl ReadViewEntity {
+    pub fn entity_id(&self) -> &EntityID {
+        self.entity.entity_id()
+    }
+
+    pub async fn get<T: KvComponent + prost::Message + Default>(&self) -> Result<Option<T>, Error> {
+        self.entity.get::<T>().await
+    }
+
+    pub async fn get_many<S: ComponentSet>(&self) -> Result<S::Output, Error> {
+        self.entity.get_many::<S>().await
+    }
+
+    pub async fn metadata(&self) -> Result<EntityMetadata, Error> {
+        self.entity.metadata().await
+    }
+
+    pub async fn revision(&self) -> Result<u64, Error> {
+        self.entity.revision().await
+    }
+
+    pub async fn edges_entity<T: KvRelation + prost::Message + Default>(
+        &self,
+        direction: RelationDirection,
+    ) -> Result<Vec<(EntityID, RelationDirection)>, Error> {
+        let mut snapshot = self.entity.client.snapshot().await?;
+        self.entity
+            .edges_entity_in_txn(T::type_path(), direction, &mut *snapshot)
+            .await
+    }
+
+    pub async fn degree<T: KvRelation + prost::Message + Default>(
+        &self,
+        direction: RelationDirection,
+    ) -> Result<u64, Error> {
+        self.entity.degree::<T>(direction).await
+    }
+
+    pub async fn relation<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<Option<T>, Error> {
+        self.entity.relation::<T>(entity_id).await
+    }
+
+    pub async fn has_relation<T: KvRelation + prost::Message + Default>(
+        &self,
+        entity_id: impl Into<EntityID>,
+    ) -> Result<bool, Error> {
+        self.entity.has_relation::<T>(entity_id).await
+    }
+
+    pub async fn edges<T: KvRelation + prost::Message + Default + 'static>(
+        &self,
+        direction: RelationDirection,
+    ) -> Pin<Box<dyn Stream<Item = Result<(EntityID, RelationDirection, T), Error>> + Send>> {
+        self.entity.edges::<T>(direction).await
+    }
+}
+
+/// 只读视图中的组件检索，提供 [`Filter`] 的组合、分页和读取操作
+pub struct ReadViewFilter<T> {
+    filter: Filter<T>,
+}
+
+impl<T> ReadViewFilter<T>
+where
+    T: KvComponent + prost::Message + Default,
+{
+    /// 由派生宏生成的检索方法调用，`client` 为视图固定了读取时间戳的 [`DB`]
+    pub fn new(client: DB, field_name: String, bound_condition: BoundCondition) -> Self {
+        Self {
+            filter: Filter::new(client, field_name, bound_condition),
+        }
+    }
+
+    pub fn and<U>(self, other: ReadViewFilter<U>) -> Self {
+        Self {
+            filter: self.filter.and(other.filter),
+        }
+    }
+
+    pub fn or<U>(self, other: ReadViewFilter<U>) -> Self {
+        Self {
+            filter: self.filter.or(other.filter),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self {
+            filter: self.filter.not(),
+        }
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        Self {
+            filter: self.filter.limit(limit),
+        }
+    }
+
+    pub fn offset(self, offset: usize) -> Self {
+        Self {
+            filter: self.filter.offset(offset),
+        }
+    }
+
+    pub fn order(self, order: Order) -> Self {
+        Self {
+            filter: self.filter.order(order),
+        }
+    }
+
+    pub fn after(self, cursor: Cursor) -> Self {
+        Self {
+            filter: self.filter.after(cursor),
+        }
+    }
+
+    pub async fn entity(&self) -> Result<ReadViewEntity, Error> {
+        Ok(ReadViewEntity {
+            entity: self.filter.entity().await?,
+        })
+    }
+
+    pub async fn single(&self) -> Result<T, Error> {
+        self.filter.single().await
+    }
+
+    pub async fn count(&self) -> Result<u64, Error> {
+        self.filter.count().await
+    }
+
+    pub async fn all(&self) -> Result<Vec<T>, Error> {
+        self.filter.all().await
+    }
+
+    pub async fn page(&self) -> Result<Page<T>, Error> {
+        self.filter.page().await
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn stream(self) -> Pin<Box<dyn Stream<Item = Result<(EntityID, T), Error>> + Send>>
+    where
+        T: 'static,
+    {
+        self.filter.stream()
+    }
+
+    pub async fn list(&self) -> Result<ReadViewList, Error> {
+        Ok(ReadViewList {
+            list: self.filter.list().await?,
+        })
+    }
+}
+
+/// 只读视图中的一组实体，提供 [`EntityListHandler`] 的读取操作
+#[derive(Clone)]
+pub struct ReadViewList {
+    list: EntityListHandler,
+}
+
+impl ReadViewList {
+    pub fn entity_ids(&self) -> &[EntityID] {
+        &self.list.entity_ids
+    }
+
+    /// 逐个返回列表中的实体
+    pub fn iter(&self) -> impl Iterator<Item = ReadViewEntity> + '_ {
+        self.list.entity_ids.iter().map(|entity_id| ReadViewEntity {
+            entity: self.list.client.entity(entity_id.clone()),
+        })
+    }
+
+    pub async fn get<T: KvComponent + prost::Message + Default>(&self) -> Result<Vec<T>, Error> {
+        self.list.get::<T>().await
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn stream<T: KvComponent + prost::Message + Default + 'static>(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = Result<(EntityID, Option<T>), Error>> + Send>> {
+        self.list.stream::<T>()
+    }
+}
@@ -0,0 +1,119 @@
+use futures::TryStreamExt;
+use kv_entity::{DB, EntityID, Error, With, Without};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Position {
+    #[prost(int32, tag = "1")]
+    pub x: i32,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Velocity {
+    #[prost(int32, tag = "1")]
+    pub dx: i32,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Frozen {
+    #[prost(bool, tag = "1")]
+    pub frozen: bool,
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+// 1: 位置和速度，2: 只有位置，3: 位置、速度且被冻结，resource: 位置和速度
+async fn setup() -> DB {
+    let db = DB::new_in_memory();
+    db.entity("1")
+        .attach((Position { x: 1 }, Velocity { dx: 10 }))
+        .await
+        .unwrap();
+    db.entity("2").attach(Position { x: 2 }).await.unwrap();
+    db.entity("3")
+        .attach((
+            Position { x: 3 },
+            Velocity { dx: 30 },
+            Frozen { frozen: true },
+        ))
+        .await
+        .unwrap();
+    db.resource()
+        .await
+        .attach((Position { x: 0 }, Velocity { dx: 0 }))
+        .await
+        .unwrap();
+    db
+}
+
+#[tokio::test]
+async fn with_and_without_select_matching_entities() {
+    let db = setup().await;
+
+    let moving = db
+        .query_archetype::<(With<Position>, With<Velocity>, Without<Frozen>)>()
+        .stream()
+        .map_ok(|(entity_id, (position, velocity, ()))| (entity_id, position.x, velocity.dx))
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(moving, [(id("1"), 1, 10), (EntityID::resource(), 0, 0)]);
+
+    let still = db
+        .query_archetype::<(Without<Velocity>, With<Position>)>()
+        .stream()
+        .map_ok(|(entity_id, _)| entity_id)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(still, [id("2")]);
+}
+
+#[tokio::test]
+async fn resource_entity_keeps_its_id() {
+    let db = setup().await;
+    let ids = db
+        .query_archetype::<(With<Velocity>,)>()
+        .stream()
+        .map_ok(|(entity_id, _)| entity_id)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(ids, [id("1"), id("3"), EntityID::resource()]);
+    // 结果中的 ID 可以直接用于后续读写
+    assert_eq!(
+        db.entity(ids[2].clone()).get::<Velocity>().await.unwrap(),
+        Some(Velocity { dx: 0 })
+    );
+}
+
+#[tokio::test]
+async fn archetype_without_with_term_is_an_error() {
+    let db = setup().await;
+    let result = db
+        .query_archetype::<(Without<Frozen>,)>()
+        .stream()
+        .try_collect::<Vec<_>>()
+        .await;
+    assert!(matches!(result, Err(Error::InvalidArchetype(_))));
+}
+
+#[tokio::test]
+async fn scans_past_the_first_page() {
+    let db = DB::new_in_memory();
+    for i in 0..300 {
+        let entity = db.entity(format!("{i:03}"));
+        entity.attach(Position { x: i }).await.unwrap();
+        if i % 3 == 0 {
+            entity.attach(Velocity { dx: i }).await.unwrap();
+        }
+    }
+    let count = db
+        .query_archetype::<(With<Position>, Without<Velocity>)>()
+        .stream()
+        .try_fold(0, |count, _| async move { Ok(count + 1) })
+        .await
+        .unwrap();
+    assert_eq!(count, 200);
+}
@@ -0,0 +1,199 @@
+use kv_entity::{Cardinality, DB, EntityID, Error, KvRelation, RelationDirection};
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(cardinality = "one_to_one")]
+pub struct Married {
+    #[prost(int32, tag = "1")]
+    pub year: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(cardinality = "one_to_many")]
+pub struct Owns {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(cardinality = "many_to_one")]
+pub struct LivesIn {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(cardinality = "one_to_one", symmetric)]
+pub struct Partner {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Knows {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+fn assert_violation<T>(result: Result<T, Error>, existing: &str) {
+    match result {
+        Err(Error::CardinalityViolation {
+            existing_entity, ..
+        }) => assert_eq!(existing_entity, id(existing)),
+        Err(error) => panic!("expected cardinality violation, got {error:?}"),
+        Ok(_) => panic!("expected cardinality violation"),
+    }
+}
+
+async fn neighbors<T: KvRelation + prost::Message + Default>(
+    db: &DB,
+    entity: &str,
+    direction: RelationDirection,
+) -> Vec<EntityID> {
+    let mut ids = db
+        .entity(entity)
+        .edges_entity::<T>(direction)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn derive_sets_cardinality() {
+    assert_eq!(Married::cardinality(), Cardinality::OneToOne);
+    assert_eq!(Owns::cardinality(), Cardinality::OneToMany);
+    assert_eq!(LivesIn::cardinality(), Cardinality::ManyToOne);
+    assert_eq!(Knows::cardinality(), Cardinality::ManyToMany);
+}
+
+#[tokio::test]
+async fn one_to_one_limits_both_ends() {
+    let db = DB::new_in_memory();
+    db.entity("a").link("b", Married { year: 1 }).await.unwrap();
+
+    assert_violation(db.entity("a").link("c", Married { year: 2 }).await, "b");
+    assert_violation(db.entity("c").link("b", Married { year: 2 }).await, "a");
+    // 重复关联同一对实体只更新关系数据
+    db.entity("a").link("b", Married { year: 3 }).await.unwrap();
+    assert_eq!(
+        db.entity("a").relation::<Married>("b").await.unwrap(),
+        Some(Married { year: 3 })
+    );
+
+    db.entity("a").unlink::<Married>("b").await.unwrap();
+    db.entity("c").link("b", Married { year: 4 }).await.unwrap();
+    db.entity("a").link("d", Married { year: 4 }).await.unwrap();
+}
+
+#[tokio::test]
+async fn one_to_many_and_many_to_one_limit_one_end() {
+    let db = DB::new_in_memory();
+    db.entity("alice")
+        .link("car", Owns { since: 1 })
+        .await
+        .unwrap();
+    db.entity("alice")
+        .link("bike", Owns { since: 1 })
+        .await
+        .unwrap();
+    assert_violation(
+        db.entity("bob").link("car", Owns { since: 2 }).await,
+        "alice",
+    );
+
+    db.entity("alice")
+        .link("paris", LivesIn { since: 1 })
+        .await
+        .unwrap();
+    db.entity("bob")
+        .link("paris", LivesIn { since: 1 })
+        .await
+        .unwrap();
+    assert_violation(
+        db.entity("alice").link("rome", LivesIn { since: 2 }).await,
+        "paris",
+    );
+
+    for i in 0..3 {
+        db.entity("a")
+            .link(format!("x{i}"), Knows { since: i })
+            .await
+            .unwrap();
+        db.entity(format!("y{i}"))
+            .link("b", Knows { since: i })
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn link_replace_unlinks_the_previous_edge() {
+    let db = DB::new_in_memory();
+    db.entity("alice")
+        .link("paris", LivesIn { since: 1 })
+        .await
+        .unwrap();
+    db.entity("alice")
+        .link_replace("rome", LivesIn { since: 2 })
+        .await
+        .unwrap();
+    assert_eq!(
+        neighbors::<LivesIn>(&db, "alice", RelationDirection::In).await,
+        [id("rome")]
+    );
+    assert!(
+        neighbors::<LivesIn>(&db, "paris", RelationDirection::Out)
+            .await
+            .is_empty()
+    );
+
+    db.entity("a").link("b", Married { year: 1 }).await.unwrap();
+    db.entity("c").link("d", Married { year: 1 }).await.unwrap();
+    // 两端都已有关系时，两条旧关系都会被解除
+    db.entity("a")
+        .link_replace("d", Married { year: 2 })
+        .await
+        .unwrap();
+    assert_eq!(
+        neighbors::<Married>(&db, "a", RelationDirection::In).await,
+        [id("d")]
+    );
+    assert!(
+        neighbors::<Married>(&db, "b", RelationDirection::Out)
+            .await
+            .is_empty()
+    );
+    assert!(
+        neighbors::<Married>(&db, "c", RelationDirection::In)
+            .await
+            .is_empty()
+    );
+    db.entity("c").link("b", Married { year: 3 }).await.unwrap();
+}
+
+#[tokio::test]
+async fn unlinking_a_missing_edge_keeps_the_limit() {
+    let db = DB::new_in_memory();
+    db.entity("a")
+        .link("b", LivesIn { since: 1 })
+        .await
+        .unwrap();
+    db.entity("a").unlink::<LivesIn>("c").await.unwrap();
+    assert_violation(db.entity("a").link("d", LivesIn { since: 2 }).await, "b");
+
+    db.entity("a")
+        .link("b", Partner { since: 1 })
+        .await
+        .unwrap();
+    db.entity("c").unlink::<Partner>("a").await.unwrap();
+    db.entity("b").unlink::<Partner>("c").await.unwrap();
+    assert_violation(db.entity("a").link("d", Partner { since: 2 }).await, "b");
+    assert_violation(db.entity("d").link("b", Partner { since: 2 }).await, "a");
+}
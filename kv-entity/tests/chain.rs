@@ -0,0 +1,145 @@
+use kv_entity::{DB, EntityID, RelationDirection};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct User {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Post {
+    #[prost(string, tag = "1")]
+    pub title: String,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Follows {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Wrote {
+    #[prost(int32, tag = "1")]
+    pub at: i32,
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+fn user(name: &str) -> User {
+    User {
+        name: name.to_string(),
+    }
+}
+
+fn post(title: &str) -> Post {
+    Post {
+        title: title.to_string(),
+    }
+}
+
+// alice 关注 bob 和 carol，bob 关注 carol；bob 写了 p1，carol 写了 p2 和 p3
+async fn setup() -> DB {
+    let db = DB::new_in_memory();
+    for name in ["alice", "bob", "carol"] {
+        db.entity(name).attach(user(name)).await.unwrap();
+    }
+    for title in ["p1", "p2", "p3"] {
+        db.entity(title).attach(post(title)).await.unwrap();
+    }
+    db.entity("alice")
+        .link("bob", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("alice")
+        .link("carol", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("bob")
+        .link("carol", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("bob").link("p1", Wrote { at: 1 }).await.unwrap();
+    db.entity("carol")
+        .link("p2", Wrote { at: 2 })
+        .await
+        .unwrap();
+    db.entity("carol")
+        .link("p3", Wrote { at: 3 })
+        .await
+        .unwrap();
+    db
+}
+
+fn titles(mut posts: Vec<(EntityID, Post)>) -> Vec<String> {
+    posts.sort_by(|a, b| a.0.cmp(&b.0));
+    posts.into_iter().map(|(_, post)| post.title).collect()
+}
+
+#[tokio::test]
+async fn follows_relations_from_a_filter() {
+    let db = setup().await;
+    let posts = db
+        .query::<User>()
+        .name("alice")
+        .follow::<Follows>(RelationDirection::In)
+        .follow::<Wrote>(RelationDirection::In)
+        .with::<Post>()
+        .collect()
+        .await
+        .unwrap();
+    assert_eq!(titles(posts), ["p1", "p2", "p3"]);
+}
+
+#[tokio::test]
+async fn follows_relations_from_an_entity() {
+    let db = setup().await;
+    let mut followers = db
+        .entity("carol")
+        .follow::<Follows>(RelationDirection::Out)
+        .with::<User>()
+        .collect()
+        .await
+        .unwrap();
+    followers.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        followers,
+        vec![(id("alice"), user("alice")), (id("bob"), user("bob"))]
+    );
+}
+
+#[tokio::test]
+async fn reached_entities_are_deduplicated() {
+    let db = setup().await;
+    // alice 和 bob 都关注了 carol
+    let chain = db
+        .query::<User>()
+        .name_prefix("")
+        .follow::<Follows>(RelationDirection::In);
+    let mut ids = chain.entity_ids().await.unwrap();
+    ids.sort();
+    assert_eq!(ids, [id("bob"), id("carol")]);
+    assert_eq!(chain.count().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn with_drops_entities_without_the_component() {
+    let db = setup().await;
+    let chain = db
+        .entity("alice")
+        .follow::<Follows>(RelationDirection::In)
+        .with::<Post>();
+    assert_eq!(chain.count().await.unwrap(), 0);
+
+    let users = db
+        .query::<User>()
+        .name("bob")
+        .with::<User>()
+        .collect()
+        .await
+        .unwrap();
+    assert_eq!(users, vec![(id("bob"), user("bob"))]);
+}
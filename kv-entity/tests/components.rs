@@ -0,0 +1,172 @@
+use kv_entity::DB;
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Name {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Health {
+    #[index]
+    #[prost(int32, tag = "1")]
+    pub hp: i32,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Armor {
+    #[prost(int32, tag = "1")]
+    pub value: i32,
+}
+
+fn name(name: &str) -> Name {
+    Name {
+        name: name.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn get_many_reads_components_together() {
+    let db = DB::new_in_memory();
+    db.entity("1")
+        .attach((name("knight"), Health { hp: 10 }))
+        .await
+        .unwrap();
+
+    let (found_name, health, armor) = db
+        .entity("1")
+        .get_many::<(Name, Health, Armor)>()
+        .await
+        .unwrap();
+    assert_eq!(found_name, Some(name("knight")));
+    assert_eq!(health, Some(Health { hp: 10 }));
+    assert_eq!(armor, None);
+
+    let (armor,) = db.entity("2").get_many::<(Armor,)>().await.unwrap();
+    assert_eq!(armor, None);
+}
+
+#[tokio::test]
+async fn get_many_sees_writes_of_the_transaction() {
+    let db = DB::new_in_memory();
+    db.entity("1").attach(name("knight")).await.unwrap();
+    let (before, after) = db
+        .transaction(|tx| async move {
+            let entity = tx.entity("1");
+            let before = entity.get_many::<(Name, Armor)>().await?;
+            entity.attach(Armor { value: 3 }).await?;
+            let after = entity.get_many::<(Name, Armor)>().await?;
+            Ok((before, after))
+        })
+        .await
+        .unwrap();
+    assert_eq!(before, (Some(name("knight")), None));
+    assert_eq!(after, (Some(name("knight")), Some(Armor { value: 3 })));
+}
+
+#[tokio::test]
+async fn update_modifies_in_place_and_keeps_index() {
+    let db = DB::new_in_memory();
+    assert!(matches!(
+        db.entity("1")
+            .update::<Health>(|health| health.hp += 1)
+            .await,
+        Err(kv_entity::Error::NotFound)
+    ));
+
+    db.entity("1").attach(Health { hp: 10 }).await.unwrap();
+    let updated = db
+        .entity("1")
+        .update::<Health>(|health| health.hp -= 3)
+        .await
+        .unwrap();
+    assert_eq!(updated, Health { hp: 7 });
+    assert_eq!(db.query::<Health>().hp(10).count().await.unwrap(), 0);
+    assert_eq!(db.query::<Health>().hp(7).count().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn upsert_with_starts_from_default() {
+    let db = DB::new_in_memory();
+    for expected in [6, 7] {
+        let health = db
+            .entity("1")
+            .upsert_with(|| Health { hp: 5 }, |health| health.hp += 1)
+            .await
+            .unwrap();
+        assert_eq!(health.hp, expected);
+    }
+    assert_eq!(db.query::<Health>().hp(7).count().await.unwrap(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_updates_are_not_lost() {
+    let db = DB::new_in_memory().with_retry_policy(kv_entity::RetryPolicy {
+        max_attempts: 100,
+        ..Default::default()
+    });
+    db.entity("1").attach(Health { hp: 0 }).await.unwrap();
+    let tasks = (0..20).map(|_| {
+        let db = db.clone();
+        tokio::spawn(async move {
+            db.entity("1")
+                .update::<Health>(|health| health.hp += 1)
+                .await
+                .unwrap();
+        })
+    });
+    for task in futures::future::join_all(tasks).await {
+        task.unwrap();
+    }
+    assert_eq!(
+        db.entity("1").get::<Health>().await.unwrap(),
+        Some(Health { hp: 20 })
+    );
+}
+
+#[tokio::test]
+async fn insert_requires_absent_components() {
+    let db = DB::new_in_memory();
+    db.entity("1").insert(name("knight")).await.unwrap();
+    assert!(matches!(
+        db.entity("1").insert(name("mage")).await,
+        Err(kv_entity::Error::AlreadyExists)
+    ));
+    // 元组中任意组件已存在时整体失败
+    assert!(matches!(
+        db.entity("1")
+            .insert((Health { hp: 1 }, name("mage")))
+            .await,
+        Err(kv_entity::Error::AlreadyExists)
+    ));
+    assert_eq!(
+        db.entity("1").get_many::<(Name, Health)>().await.unwrap(),
+        (Some(name("knight")), None)
+    );
+    db.entity("1")
+        .insert((Health { hp: 1 }, Armor { value: 2 }))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn replace_requires_existing_components() {
+    let db = DB::new_in_memory();
+    assert!(matches!(
+        db.entity("1").replace(name("knight")).await,
+        Err(kv_entity::Error::NotFound)
+    ));
+    db.entity("1").attach(name("knight")).await.unwrap();
+    assert!(matches!(
+        db.entity("1")
+            .replace((name("mage"), Health { hp: 1 }))
+            .await,
+        Err(kv_entity::Error::NotFound)
+    ));
+    assert_eq!(db.query::<Name>().name("knight").count().await.unwrap(), 1);
+
+    db.entity("1").replace(name("mage")).await.unwrap();
+    assert_eq!(db.query::<Name>().name("knight").count().await.unwrap(), 0);
+    assert_eq!(db.query::<Name>().name("mage").count().await.unwrap(), 1);
+}
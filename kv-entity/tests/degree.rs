@@ -0,0 +1,193 @@
+use kv_entity::{DB, RelationDirection};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct User {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Follows {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(symmetric)]
+pub struct Friend {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(cardinality = "many_to_one")]
+pub struct LivesIn {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+async fn setup(names: &[&str]) -> DB {
+    let db = DB::new_in_memory();
+    for name in names {
+        db.entity(*name)
+            .attach(User {
+                name: name.to_string(),
+            })
+            .await
+            .unwrap();
+    }
+    db
+}
+
+// (In, Out, Both) 三个方向上的度数
+async fn degrees<T: kv_entity::KvRelation + prost::Message + Default>(
+    db: &DB,
+    entity: &str,
+) -> (u64, u64, u64) {
+    let entity = db.entity(entity);
+    (
+        entity.degree::<T>(RelationDirection::In).await.unwrap(),
+        entity.degree::<T>(RelationDirection::Out).await.unwrap(),
+        entity.degree::<T>(RelationDirection::Both).await.unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn link_and_unlink_maintain_both_ends() {
+    let db = setup(&["a", "b", "c"]).await;
+    assert_eq!(degrees::<Follows>(&db, "a").await, (0, 0, 0));
+
+    db.entity("a")
+        .link("b", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("a")
+        .link("c", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("b")
+        .link("a", Follows { since: 1 })
+        .await
+        .unwrap();
+    // 重复关联同一对实体不会重复计数
+    db.entity("a")
+        .link("b", Follows { since: 2 })
+        .await
+        .unwrap();
+    assert_eq!(degrees::<Follows>(&db, "a").await, (2, 1, 3));
+    assert_eq!(degrees::<Follows>(&db, "b").await, (1, 1, 2));
+    assert_eq!(degrees::<Follows>(&db, "c").await, (0, 1, 1));
+
+    db.entity("a").unlink::<Follows>("b").await.unwrap();
+    // 解除不存在的关系不会减少计数
+    db.entity("a").unlink::<Follows>("b").await.unwrap();
+    assert_eq!(degrees::<Follows>(&db, "a").await, (1, 1, 2));
+    assert_eq!(degrees::<Follows>(&db, "b").await, (1, 0, 1));
+    assert_eq!(degrees::<Friend>(&db, "a").await, (0, 0, 0));
+}
+
+#[tokio::test]
+async fn deletes_release_the_counters_of_the_other_end() {
+    let db = setup(&["a", "b", "c"]).await;
+    db.entity("a")
+        .link("b", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("c")
+        .link("b", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("b")
+        .link("a", Follows { since: 1 })
+        .await
+        .unwrap();
+
+    db.entity("b").delete_edges::<Follows>().await.unwrap();
+    assert_eq!(degrees::<Follows>(&db, "a").await, (0, 0, 0));
+    assert_eq!(degrees::<Follows>(&db, "b").await, (0, 0, 0));
+    assert_eq!(degrees::<Follows>(&db, "c").await, (0, 0, 0));
+
+    db.entity("a")
+        .link("b", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("c")
+        .link("b", Follows { since: 1 })
+        .await
+        .unwrap();
+    db.entity("b").delete().await.unwrap();
+    assert_eq!(degrees::<Follows>(&db, "a").await, (0, 0, 0));
+    assert_eq!(degrees::<Follows>(&db, "c").await, (0, 0, 0));
+    assert_eq!(degrees::<Follows>(&db, "b").await, (0, 0, 0));
+}
+
+#[tokio::test]
+async fn symmetric_relations_count_each_edge_once() {
+    let db = setup(&["a", "b", "c"]).await;
+    db.entity("a").link("b", Friend { since: 1 }).await.unwrap();
+    db.entity("b").link("a", Friend { since: 2 }).await.unwrap();
+    db.entity("c").link("a", Friend { since: 1 }).await.unwrap();
+    for direction in [
+        RelationDirection::In,
+        RelationDirection::Out,
+        RelationDirection::Both,
+    ] {
+        assert_eq!(db.entity("a").degree::<Friend>(direction).await.unwrap(), 2);
+    }
+    assert_eq!(
+        db.entity("b")
+            .degree::<Friend>(RelationDirection::Both)
+            .await
+            .unwrap(),
+        1
+    );
+
+    db.entity("b").unlink::<Friend>("a").await.unwrap();
+    db.entity("c").delete().await.unwrap();
+    assert_eq!(
+        db.entity("a")
+            .degree::<Friend>(RelationDirection::Both)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn link_replace_moves_the_counter() {
+    let db = setup(&["alice", "paris", "rome"]).await;
+    db.entity("alice")
+        .link("paris", LivesIn { since: 1 })
+        .await
+        .unwrap();
+    db.entity("alice")
+        .link_replace("rome", LivesIn { since: 2 })
+        .await
+        .unwrap();
+    assert_eq!(degrees::<LivesIn>(&db, "alice").await, (1, 0, 1));
+    assert_eq!(degrees::<LivesIn>(&db, "paris").await, (0, 0, 0));
+    assert_eq!(degrees::<LivesIn>(&db, "rome").await, (0, 1, 1));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_links_are_all_counted() {
+    let db = setup(&["hub"])
+        .await
+        .with_retry_policy(kv_entity::RetryPolicy {
+            max_attempts: 100,
+            ..Default::default()
+        });
+    let links = (0..10).map(|i| {
+        let db = db.clone();
+        tokio::spawn(async move {
+            db.entity(format!("n{i}"))
+                .link("hub", Follows { since: i })
+                .await
+                .unwrap();
+        })
+    });
+    for link in links.collect::<Vec<_>>() {
+        link.await.unwrap();
+    }
+    assert_eq!(degrees::<Follows>(&db, "hub").await, (0, 10, 10));
+}
@@ -0,0 +1,212 @@
+use kv_entity::{DB, DeletePolicy, EntityID, Error, KvRelation, RelationDirection};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Node {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(on_delete = "cascade")]
+pub struct Owns {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(on_delete = "restrict")]
+pub struct DependsOn {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Mentions {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+fn node(name: &str) -> Node {
+    Node {
+        name: name.to_string(),
+    }
+}
+
+async fn setup(names: &[&str]) -> DB {
+    let db = DB::new_in_memory();
+    for name in names {
+        db.entity(*name).attach(node(name)).await.unwrap();
+    }
+    db
+}
+
+async fn exists(db: &DB, name: &str) -> bool {
+    db.entity(name).get::<Node>().await.unwrap().is_some()
+}
+
+fn assert_restricted<T>(result: Result<T, Error>, existing: &str) {
+    match result {
+        Err(Error::DeleteRestricted {
+            existing_entity, ..
+        }) => assert_eq!(existing_entity, id(existing)),
+        Err(error) => panic!("expected restricted delete, got {error:?}"),
+        Ok(_) => panic!("expected restricted delete"),
+    }
+}
+
+#[test]
+fn derive_sets_delete_policy() {
+    assert_eq!(Owns::delete_policy(), DeletePolicy::Cascade);
+    assert_eq!(DependsOn::delete_policy(), DeletePolicy::Restrict);
+    assert_eq!(Mentions::delete_policy(), DeletePolicy::Detach);
+}
+
+#[tokio::test]
+async fn detach_keeps_the_other_end() {
+    let db = setup(&["a", "b"]).await;
+    db.entity("a")
+        .link("b", Mentions { since: 1 })
+        .await
+        .unwrap();
+    db.entity("b")
+        .link("a", Mentions { since: 2 })
+        .await
+        .unwrap();
+
+    db.entity("a").delete().await.unwrap();
+    assert!(exists(&db, "b").await);
+    assert!(
+        db.entity("b")
+            .edges_entity::<Mentions>(RelationDirection::Both)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn cascade_deletes_linked_entities_recursively() {
+    // a -> b -> c -> a 形成环，d 只是被 b 提及
+    let db = setup(&["a", "b", "c", "d", "x"]).await;
+    db.entity("a").link("b", Owns { since: 1 }).await.unwrap();
+    db.entity("b").link("c", Owns { since: 1 }).await.unwrap();
+    db.entity("c").link("a", Owns { since: 1 }).await.unwrap();
+    db.entity("b")
+        .link("d", Mentions { since: 1 })
+        .await
+        .unwrap();
+    // x 拥有 a，删除 a 不会反向删除 x
+    db.entity("x").link("a", Owns { since: 1 }).await.unwrap();
+
+    db.entity("b").delete().await.unwrap();
+    for name in ["a", "b", "c"] {
+        assert!(!exists(&db, name).await, "{name}");
+    }
+    assert!(exists(&db, "d").await);
+    assert!(exists(&db, "x").await);
+    assert!(
+        db.entity("x")
+            .edges_entity::<Owns>(RelationDirection::Both)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(db.query::<Node>().name_prefix("").count().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn restrict_refuses_while_inbound_edges_exist() {
+    let db = setup(&["app", "lib"]).await;
+    db.entity("app")
+        .link("lib", DependsOn { since: 1 })
+        .await
+        .unwrap();
+
+    assert_restricted(db.entity("lib").delete().await, "app");
+    assert!(exists(&db, "lib").await);
+    assert!(
+        db.entity("app")
+            .has_relation::<DependsOn>("lib")
+            .await
+            .unwrap()
+    );
+
+    // 依赖方可以删除，删除后被依赖的实体也可以删除
+    db.entity("app").delete().await.unwrap();
+    db.entity("lib").delete().await.unwrap();
+    assert!(!exists(&db, "lib").await);
+}
+
+#[tokio::test]
+async fn cascade_is_restricted_by_entities_outside_the_delete() {
+    let db = setup(&["a", "b", "c"]).await;
+    db.entity("a").link("b", Owns { since: 1 }).await.unwrap();
+    db.entity("c")
+        .link("b", DependsOn { since: 1 })
+        .await
+        .unwrap();
+
+    assert_restricted(db.entity("a").delete().await, "c");
+    for name in ["a", "b", "c"] {
+        assert!(exists(&db, name).await, "{name}");
+    }
+
+    // 依赖方也在同一次删除中时不受限制
+    db.entity("c").unlink::<DependsOn>("b").await.unwrap();
+    db.entity("a").link("c", Owns { since: 1 }).await.unwrap();
+    db.entity("b")
+        .link("c", DependsOn { since: 1 })
+        .await
+        .unwrap();
+    db.entity("a").delete().await.unwrap();
+    for name in ["a", "b", "c"] {
+        assert!(!exists(&db, name).await, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn policies_apply_to_list_and_transaction_deletes() {
+    let db = setup(&["a", "b", "c", "d", "e"]).await;
+    db.entity("a").link("c", Owns { since: 1 }).await.unwrap();
+    db.entity("e")
+        .link("b", DependsOn { since: 1 })
+        .await
+        .unwrap();
+
+    let list = db
+        .query::<Node>()
+        .name("a")
+        .or(db.query::<Node>().name("b"));
+    assert_restricted(list.list().await.unwrap().delete().await, "e");
+    assert!(exists(&db, "a").await);
+
+    db.query::<Node>()
+        .name("a")
+        .list()
+        .await
+        .unwrap()
+        .delete()
+        .await
+        .unwrap();
+    assert!(!exists(&db, "c").await);
+
+    let result = db
+        .transaction(|txn| async move { txn.entity("b").delete().await })
+        .await;
+    assert_restricted(result, "e");
+    db.transaction(|txn| async move {
+        txn.entity("e").delete().await?;
+        txn.entity("b").delete().await
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        db.query::<Node>().name_prefix("").all().await.unwrap(),
+        [node("d")]
+    );
+}
@@ -0,0 +1,401 @@
+use kv_entity::DB;
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Person {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[index]
+    #[prost(int32, tag = "2")]
+    pub age: i32,
+}
+
+fn person(name: &str, age: i32) -> Person {
+    Person {
+        name: name.to_string(),
+        age,
+    }
+}
+
+async fn setup() -> DB {
+    let db = DB::new_in_memory();
+    for (id, name, age) in [
+        ("1", "alice", -5),
+        ("2", "alan", 0),
+        ("3", "bob", 7),
+        ("4", "bobby", 10),
+        ("5", "carol", 42),
+    ] {
+        db.entity(id).attach(person(name, age)).await.unwrap();
+    }
+    db
+}
+
+fn names(people: Vec<Person>) -> Vec<String> {
+    people.into_iter().map(|person| person.name).collect()
+}
+
+#[tokio::test]
+async fn range_queries_follow_numeric_order() {
+    let db = setup().await;
+    let query =
+        |filter: kv_entity::Filter<Person>| async move { names(filter.all().await.unwrap()) };
+
+    assert_eq!(
+        query(db.query::<Person>().age_range(0..10)).await,
+        ["alan", "bob"]
+    );
+    assert_eq!(
+        query(db.query::<Person>().age_range(0..=10)).await,
+        ["alan", "bob", "bobby"]
+    );
+    assert_eq!(
+        query(db.query::<Person>().age_range(..7)).await,
+        ["alice", "alan"]
+    );
+    assert_eq!(
+        query(db.query::<Person>().age_range(7..)).await,
+        ["bob", "bobby", "carol"]
+    );
+    assert_eq!(
+        query(db.query::<Person>().age_gt(7)).await,
+        ["bobby", "carol"]
+    );
+    assert_eq!(query(db.query::<Person>().age_lt(0)).await, ["alice"]);
+    assert_eq!(
+        query(db.query::<Person>().age_between(-5, 7)).await,
+        ["alice", "alan", "bob"]
+    );
+    assert!(
+        query(db.query::<Person>().age_between(10, 7))
+            .await
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn prefix_queries_match_string_prefix() {
+    let db = setup().await;
+    assert_eq!(
+        names(db.query::<Person>().name_prefix("bob").all().await.unwrap()),
+        ["bob", "bobby"]
+    );
+    assert_eq!(
+        names(db.query::<Person>().name_prefix("al").all().await.unwrap()),
+        ["alan", "alice"]
+    );
+    assert_eq!(db.query::<Person>().name("bob").count().await.unwrap(), 1);
+    assert_eq!(
+        db.query::<Person>().name_prefix("z").count().await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn range_queries_reflect_updates() {
+    let db = setup().await;
+    db.entity("3").attach(person("bob", 50)).await.unwrap();
+    assert_eq!(
+        db.query::<Person>().age_range(0..10).count().await.unwrap(),
+        1
+    );
+    assert_eq!(
+        db.query::<Person>().age_gt(42).single().await.unwrap().name,
+        "bob"
+    );
+}
+
+#[tokio::test]
+async fn paged_scans_do_not_skip_ids_sharing_a_prefix() {
+    use futures::StreamExt;
+
+    let db = DB::new_in_memory();
+    // 每页 128 条，第一页以 e-1 结尾，下一页不能跳过 e-10 这类以其为前缀的 id
+    let ids = (0..127)
+        .map(|i| format!("0{i:03}"))
+        .chain(["1".to_string()])
+        .chain((10..20).map(|i| i.to_string()));
+    for id in ids {
+        db.entity(id).attach(person("x", 1)).await.unwrap();
+    }
+    assert_eq!(db.get::<Person>().count().await, 138);
+    assert_eq!(db.query::<Person>().name("x").count().await.unwrap(), 138);
+    assert_eq!(
+        db.query::<Person>().name("x").all().await.unwrap().len(),
+        138
+    );
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Member {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub team: String,
+}
+
+async fn setup_teams(db: &DB) {
+    for (id, team) in [("1", "red"), ("3", "red"), ("4", "blue"), ("6", "blue")] {
+        db.entity(id)
+            .attach(Member {
+                team: team.to_string(),
+            })
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn and_or_not_combine_conditions() {
+    let db = setup().await;
+    let query =
+        |filter: kv_entity::Filter<Person>| async move { names(filter.all().await.unwrap()) };
+
+    let adults = || db.query::<Person>().age_gt(5);
+    let b_names = || db.query::<Person>().name_prefix("b");
+    assert_eq!(query(adults().and(b_names())).await, ["bob", "bobby"]);
+    assert_eq!(
+        query(db.query::<Person>().age_lt(7).or(b_names())).await,
+        ["alice", "alan", "bob", "bobby"]
+    );
+    assert_eq!(query(b_names().not()).await, ["alice", "alan", "carol"]);
+    assert_eq!(query(adults().and(b_names().not())).await, ["carol"]);
+    assert_eq!(adults().or(b_names()).count().await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn conditions_can_span_components() {
+    let db = setup().await;
+    setup_teams(&db).await;
+
+    let red = db.query::<Member>().team("red");
+    let found = db.query::<Person>().age_gt(0).and(red).all().await.unwrap();
+    assert_eq!(names(found), ["bob"]);
+
+    // 6 号实体没有 `Person`，读取组件数据时被忽略
+    let blue = db.query::<Member>().team("blue");
+    let found = db
+        .query::<Person>()
+        .name("carol")
+        .or(blue)
+        .all()
+        .await
+        .unwrap();
+    assert_eq!(names(found), ["bobby", "carol"]);
+
+    let red = db.query::<Member>().team("red");
+    assert_eq!(
+        db.query::<Person>()
+            .age_gt(100)
+            .or(red)
+            .count()
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+async fn collect_pages(filter: impl Fn() -> kv_entity::Filter<Person>) -> Vec<Vec<String>> {
+    let mut pages = Vec::new();
+    let mut cursor: Option<kv_entity::Cursor> = None;
+    loop {
+        let mut page_filter = filter();
+        if let Some(cursor) = cursor {
+            // 游标经过字符串往返，模拟交给客户端后再传回
+            page_filter = page_filter.after(cursor.to_string().parse().unwrap());
+        }
+        let page = page_filter.page().await.unwrap();
+        pages.push(
+            page.items
+                .into_iter()
+                .map(|(_, person)| person.name)
+                .collect(),
+        );
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    pages
+}
+
+#[tokio::test]
+async fn limit_offset_and_order() {
+    let db = setup().await;
+    let all = || db.query::<Person>().age_range(..);
+
+    assert_eq!(
+        names(all().limit(2).all().await.unwrap()),
+        ["alice", "alan"]
+    );
+    assert_eq!(
+        names(all().offset(1).limit(2).all().await.unwrap()),
+        ["alan", "bob"]
+    );
+    assert_eq!(
+        names(
+            all()
+                .order(kv_entity::Order::Desc)
+                .limit(2)
+                .all()
+                .await
+                .unwrap()
+        ),
+        ["carol", "bobby"]
+    );
+    assert_eq!(
+        names(
+            all()
+                .order(kv_entity::Order::Desc)
+                .offset(4)
+                .all()
+                .await
+                .unwrap()
+        ),
+        ["alice"]
+    );
+    assert!(all().offset(5).all().await.unwrap().is_empty());
+    // count 不受分页条件影响
+    assert_eq!(all().limit(1).count().await.unwrap(), 5);
+}
+
+#[tokio::test]
+async fn cursor_pages_through_results() {
+    let db = setup().await;
+
+    let pages = collect_pages(|| db.query::<Person>().age_range(..).limit(2)).await;
+    assert_eq!(
+        pages,
+        [vec!["alice", "alan"], vec!["bob", "bobby"], vec!["carol"]]
+    );
+
+    let pages = collect_pages(|| {
+        db.query::<Person>()
+            .age_range(..)
+            .order(kv_entity::Order::Desc)
+            .limit(3)
+    })
+    .await;
+    assert_eq!(
+        pages,
+        [vec!["carol", "bobby", "bob"], vec!["alan", "alice"]]
+    );
+
+    // 组合条件按实体 ID 排序分页
+    let pages = collect_pages(|| db.query::<Person>().name_prefix("b").not().limit(2)).await;
+    assert_eq!(pages, [vec!["alice", "alan"], vec!["carol"]]);
+
+    // 结果数量正好是 limit 的整数倍时，最后一页为空
+    let pages = collect_pages(|| db.query::<Person>().name_prefix("b").limit(2)).await;
+    assert_eq!(pages, [vec!["bob", "bobby"], vec![]]);
+}
+
+#[tokio::test]
+async fn invalid_cursor_is_rejected() {
+    for cursor in ["abc", "zz", "é0"] {
+        assert!(matches!(
+            cursor.parse::<kv_entity::Cursor>(),
+            Err(kv_entity::Error::InvalidCursor(_))
+        ));
+    }
+}
+
+async fn setup_many(db: &DB, count: i32) {
+    for i in 0..count {
+        db.entity(format!("{i:04}"))
+            .attach(person(if i % 2 == 0 { "even" } else { "odd" }, i))
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn stream_yields_every_page_in_order() {
+    use futures::TryStreamExt;
+
+    let db = DB::new_in_memory();
+    setup_many(&db, 300).await;
+
+    let ages = db
+        .query::<Person>()
+        .age_range(..)
+        .stream()
+        .map_ok(|(_, person)| person.age)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(ages, (0..300).collect::<Vec<_>>());
+
+    let ages = db
+        .query::<Person>()
+        .age_range(..)
+        .order(kv_entity::Order::Desc)
+        .offset(10)
+        .limit(200)
+        .stream()
+        .map_ok(|(_, person)| person.age)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(ages, (90..290).rev().collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn stream_follows_cursor_and_compound_conditions() {
+    use futures::TryStreamExt;
+
+    let db = DB::new_in_memory();
+    setup_many(&db, 300).await;
+
+    let page = db
+        .query::<Person>()
+        .age_range(..)
+        .limit(150)
+        .page()
+        .await
+        .unwrap();
+    let rest = db
+        .query::<Person>()
+        .age_range(..)
+        .after(page.cursor.unwrap())
+        .stream()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(rest.len(), 150);
+    assert_eq!(rest[0].1.age, 150);
+
+    let odd = db
+        .query::<Person>()
+        .age_gt(100)
+        .and(db.query::<Person>().name("odd"))
+        .stream()
+        .map_ok(|(_, person)| person.age)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(odd, (101..300).step_by(2).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn list_stream_reports_missing_components() {
+    use futures::TryStreamExt;
+
+    let db = setup().await;
+    let list = kv_entity::EntityListHandler::new(
+        ["1", "9", "5"]
+            .into_iter()
+            .map(|id| kv_entity::EntityID::new(id.to_string()))
+            .collect(),
+        db.clone(),
+    );
+    let found = list
+        .stream::<Person>()
+        .map_ok(|(_, person)| person.map(|person| person.name))
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(
+        found,
+        [Some("alice".to_string()), None, Some("carol".to_string())]
+    );
+}
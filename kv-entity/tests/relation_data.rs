@@ -0,0 +1,126 @@
+use kv_entity::{DB, Error};
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Employs {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub role: String,
+}
+
+fn employs(role: &str) -> Employs {
+    Employs {
+        role: role.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn relation_is_read_from_the_linking_side() {
+    let db = DB::new_in_memory();
+    db.entity("acme")
+        .link("alice", employs("engineer"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        db.entity("acme")
+            .relation::<Employs>("alice")
+            .await
+            .unwrap(),
+        Some(employs("engineer"))
+    );
+    assert!(
+        db.entity("acme")
+            .has_relation::<Employs>("alice")
+            .await
+            .unwrap()
+    );
+    // 反方向没有关系
+    assert_eq!(
+        db.entity("alice")
+            .relation::<Employs>("acme")
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(
+        !db.entity("alice")
+            .has_relation::<Employs>("acme")
+            .await
+            .unwrap()
+    );
+    assert!(
+        !db.entity("acme")
+            .has_relation::<Employs>("bob")
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn update_relation_rewrites_data_and_index() {
+    let db = DB::new_in_memory();
+    db.entity("acme")
+        .link("alice", employs("engineer"))
+        .await
+        .unwrap();
+
+    let updated = db
+        .entity("acme")
+        .update_relation::<Employs>("alice", |employs| employs.role = "manager".to_string())
+        .await
+        .unwrap();
+    assert_eq!(updated, employs("manager"));
+    assert_eq!(
+        db.entity("acme")
+            .relation::<Employs>("alice")
+            .await
+            .unwrap(),
+        Some(employs("manager"))
+    );
+    assert_eq!(
+        db.query_relation::<Employs>()
+            .role("engineer")
+            .count()
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        db.query_relation::<Employs>()
+            .role("manager")
+            .count()
+            .await
+            .unwrap(),
+        1
+    );
+
+    assert!(matches!(
+        db.entity("alice")
+            .update_relation::<Employs>("acme", |_| {})
+            .await,
+        Err(Error::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn unlink_removes_the_data() {
+    let db = DB::new_in_memory();
+    db.entity("acme")
+        .link("alice", employs("engineer"))
+        .await
+        .unwrap();
+    db.entity("acme").unlink::<Employs>("alice").await.unwrap();
+    assert_eq!(
+        db.entity("acme")
+            .relation::<Employs>("alice")
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(matches!(
+        db.entity("acme")
+            .update_relation::<Employs>("alice", |_| {})
+            .await,
+        Err(Error::NotFound)
+    ));
+}
@@ -0,0 +1,123 @@
+use kv_entity::{DB, EntityID};
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Rated {
+    #[index]
+    #[prost(int32, tag = "1")]
+    pub stars: i32,
+    #[index]
+    #[prost(string, tag = "2")]
+    pub tag: String,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Movie {
+    #[prost(string, tag = "1")]
+    pub title: String,
+}
+
+fn rated(stars: i32, tag: &str) -> Rated {
+    Rated {
+        stars,
+        tag: tag.to_string(),
+    }
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+async fn setup() -> DB {
+    let db = DB::new_in_memory();
+    for movie in ["x", "y"] {
+        db.entity(movie)
+            .attach(Movie {
+                title: movie.to_string(),
+            })
+            .await
+            .unwrap();
+    }
+    db.entity("a").link("x", rated(5, "great")).await.unwrap();
+    db.entity("a").link("y", rated(2, "meh")).await.unwrap();
+    db.entity("b").link("x", rated(4, "good")).await.unwrap();
+    db
+}
+
+#[tokio::test]
+async fn queries_return_both_endpoints_and_data() {
+    let db = setup().await;
+
+    let found = db
+        .query_relation::<Rated>()
+        .stars_range(4..)
+        .all()
+        .await
+        .unwrap();
+    assert_eq!(
+        found,
+        vec![
+            (id("b"), id("x"), rated(4, "good")),
+            (id("a"), id("x"), rated(5, "great")),
+        ]
+    );
+    assert_eq!(
+        db.query_relation::<Rated>()
+            .tag("meh")
+            .single()
+            .await
+            .unwrap(),
+        (id("a"), id("y"), rated(2, "meh"))
+    );
+    assert_eq!(
+        db.query_relation::<Rated>()
+            .tag_prefix("g")
+            .count()
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn index_follows_update_unlink_and_delete() {
+    let db = setup().await;
+
+    db.entity("a")
+        .update_relation::<Rated>("y", |rated| rated.stars = 3)
+        .await
+        .unwrap();
+    assert_eq!(
+        db.query_relation::<Rated>().stars(2).count().await.unwrap(),
+        0
+    );
+    assert_eq!(
+        db.query_relation::<Rated>().stars(3).count().await.unwrap(),
+        1
+    );
+
+    db.entity("a").link("x", rated(1, "great")).await.unwrap();
+    assert_eq!(
+        db.query_relation::<Rated>().stars(5).count().await.unwrap(),
+        0
+    );
+
+    db.entity("a").unlink::<Rated>("x").await.unwrap();
+    assert_eq!(
+        db.query_relation::<Rated>()
+            .tag("great")
+            .count()
+            .await
+            .unwrap(),
+        0
+    );
+
+    db.entity("x").delete().await.unwrap();
+    assert_eq!(
+        db.query_relation::<Rated>()
+            .stars_range(..)
+            .all()
+            .await
+            .unwrap(),
+        vec![(id("a"), id("y"), rated(3, "meh"))]
+    );
+}
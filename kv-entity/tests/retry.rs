@@ -0,0 +1,325 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use kv_entity::{DB, Error, MemoryBackend, RetryPolicy, Snapshot, StorageBackend, Transaction};
+use tikv_client::{Key, KvPair, Timestamp, Value, proto::kvrpcpb};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Counter {
+    #[prost(int32, tag = "1")]
+    pub value: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Watches {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+/// 在前 `conflicts` 次尝试中，于读取之后由另一个事务修改同一个组件，使本次提交冲突
+async fn increment(db: &DB, conflicts: u32) -> Result<(i32, u32), Error> {
+    let calls = AtomicU32::new(0);
+    let calls = &calls;
+    db.transaction_with_attempts(|tx| async move {
+        let entity = tx.entity("counter");
+        let value = entity.get::<Counter>().await?.unwrap_or_default().value;
+        if calls.fetch_add(1, Ordering::SeqCst) < conflicts {
+            db.entity("counter")
+                .attach(Counter { value: value + 100 })
+                .await?;
+        }
+        entity.attach(Counter { value: value + 1 }).await?;
+        Ok(value + 1)
+    })
+    .await
+}
+
+/// 可以注入故障的存储，其余操作转发给内存存储
+#[derive(Clone, Default)]
+struct FaultyBackend {
+    inner: MemoryBackend,
+    /// 接下来这么多次提交返回写冲突
+    conflicts: Arc<AtomicU32>,
+    /// 回滚是否总是失败
+    fail_rollback: bool,
+}
+
+struct FaultyTransaction {
+    inner: Box<dyn Transaction>,
+    backend: FaultyBackend,
+}
+
+#[async_trait]
+impl StorageBackend for FaultyBackend {
+    async fn begin_optimistic(&self) -> Result<Box<dyn Transaction>, Error> {
+        Ok(Box::new(FaultyTransaction {
+            inner: self.inner.begin_optimistic().await?,
+            backend: self.clone(),
+        }))
+    }
+
+    async fn begin_pessimistic(&self) -> Result<Box<dyn Transaction>, Error> {
+        Ok(Box::new(FaultyTransaction {
+            inner: self.inner.begin_pessimistic().await?,
+            backend: self.clone(),
+        }))
+    }
+
+    async fn current_timestamp(&self) -> Result<Timestamp, Error> {
+        self.inner.current_timestamp().await
+    }
+
+    fn snapshot(&self, timestamp: Timestamp) -> Box<dyn Snapshot> {
+        self.inner.snapshot(timestamp)
+    }
+}
+
+#[async_trait]
+impl Snapshot for FaultyTransaction {
+    async fn get(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        self.inner.get(key).await
+    }
+
+    async fn batch_get(&mut self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error> {
+        self.inner.batch_get(keys).await
+    }
+
+    async fn scan(
+        &mut self,
+        range: std::ops::Range<Key>,
+        limit: u32,
+    ) -> Result<Vec<KvPair>, Error> {
+        self.inner.scan(range, limit).await
+    }
+
+    async fn scan_keys(
+        &mut self,
+        range: std::ops::Range<Key>,
+        limit: u32,
+    ) -> Result<Vec<Key>, Error> {
+        self.inner.scan_keys(range, limit).await
+    }
+
+    async fn scan_reverse(
+        &mut self,
+        range: std::ops::Range<Key>,
+        limit: u32,
+    ) -> Result<Vec<KvPair>, Error> {
+        self.inner.scan_reverse(range, limit).await
+    }
+}
+
+#[async_trait]
+impl Transaction for FaultyTransaction {
+    async fn get_for_update(&mut self, key: Key) -> Result<Option<Value>, Error> {
+        self.inner.get_for_update(key).await
+    }
+
+    async fn put(&mut self, key: Key, value: Value) -> Result<(), Error> {
+        self.inner.put(key, value).await
+    }
+
+    async fn delete(&mut self, key: Key) -> Result<(), Error> {
+        self.inner.delete(key).await
+    }
+
+    async fn batch_mutate(&mut self, mutations: Vec<kvrpcpb::Mutation>) -> Result<(), Error> {
+        self.inner.batch_mutate(mutations).await
+    }
+
+    async fn commit(&mut self) -> Result<(), Error> {
+        let conflicts = &self.backend.conflicts;
+        if conflicts
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            self.inner.rollback().await?;
+            return Err(Error::WriteConflict("injected".to_string()));
+        }
+        self.inner.commit().await
+    }
+
+    async fn rollback(&mut self) -> Result<(), Error> {
+        self.inner.rollback().await?;
+        if self.backend.fail_rollback {
+            return Err(Error::InvalidEntityId("rollback".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn fast_retry(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy::default()
+        .max_attempts(max_attempts)
+        .backoff(Duration::from_millis(1), Duration::from_millis(1))
+}
+
+#[tokio::test]
+async fn reports_attempts_on_success() {
+    let db = DB::new_in_memory().with_retry_policy(fast_retry(5));
+    db.entity("counter")
+        .attach(Counter { value: 0 })
+        .await
+        .unwrap();
+
+    assert_eq!(increment(&db, 0).await.unwrap(), (1, 1));
+    // 第一次尝试冲突后重新读取到另一个事务写入的 101
+    assert_eq!(increment(&db, 1).await.unwrap(), (102, 2));
+}
+
+#[tokio::test]
+async fn exhausted_retries_report_attempts() {
+    let db = DB::new_in_memory().with_retry_policy(fast_retry(3));
+    db.entity("counter")
+        .attach(Counter { value: 0 })
+        .await
+        .unwrap();
+
+    match increment(&db, u32::MAX).await {
+        Err(Error::RetryExhausted { attempts, source }) => {
+            assert_eq!(attempts, 3);
+            assert!(matches!(*source, Error::WriteConflict(_)));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn no_retry_returns_conflict() {
+    let db = DB::new_in_memory().with_retry_policy(RetryPolicy::none());
+    db.entity("counter")
+        .attach(Counter { value: 0 })
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        increment(&db, 1).await,
+        Err(Error::WriteConflict(_))
+    ));
+}
+
+#[tokio::test]
+async fn non_retryable_errors_are_not_retried() {
+    let db = DB::new_in_memory().with_retry_policy(fast_retry(5));
+    let calls = AtomicU32::new(0);
+    let calls = &calls;
+    let result: Result<(), Error> = db
+        .transaction(|_| async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(Error::NotFound)
+        })
+        .await;
+    assert!(matches!(result, Err(Error::NotFound)));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn failed_rollback_keeps_the_original_error() {
+    let db = DB::with_backend(FaultyBackend {
+        fail_rollback: true,
+        ..Default::default()
+    })
+    .with_retry_policy(fast_retry(5));
+
+    let result: Result<(), Error> = db.transaction(|_| async { Err(Error::NotFound) }).await;
+    assert!(matches!(result, Err(Error::NotFound)));
+
+    // 回滚失败不影响按原始错误重试
+    let calls = AtomicU32::new(0);
+    let calls = &calls;
+    let (value, attempts) = db
+        .transaction_with_attempts(|_| async move {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(Error::WriteConflict("counter".to_string()));
+            }
+            Ok(1)
+        })
+        .await
+        .unwrap();
+    assert_eq!((value, attempts), (1, 2));
+}
+
+#[tokio::test]
+async fn write_paths_report_attempts() {
+    let backend = FaultyBackend::default();
+    let db = DB::with_backend(backend.clone()).with_retry_policy(fast_retry(5));
+    let conflict_once = || backend.conflicts.store(1, Ordering::SeqCst);
+    let counter = Counter { value: 1 };
+
+    let (_, attempts) = db
+        .entity("a")
+        .attach_with_attempts(counter.clone())
+        .await
+        .unwrap();
+    assert_eq!(attempts, 1);
+    conflict_once();
+    let (_, attempts) = db
+        .entity("b")
+        .attach_with_attempts(counter.clone())
+        .await
+        .unwrap();
+    assert_eq!(attempts, 2);
+
+    conflict_once();
+    let (_, attempts) = db
+        .entity("a")
+        .link_with_attempts("b", Watches { since: 2 })
+        .await
+        .unwrap();
+    assert_eq!(attempts, 2);
+    assert_eq!(
+        db.entity("a").relation::<Watches>("b").await.unwrap(),
+        Some(Watches { since: 2 })
+    );
+
+    conflict_once();
+    let list = db.get_entity::<Counter>().await.unwrap();
+    let (_, attempts) = list
+        .attach_with_attempts(Counter { value: 3 })
+        .await
+        .unwrap();
+    assert_eq!(attempts, 2);
+    assert_eq!(
+        list.get::<Counter>().await.unwrap(),
+        [Counter { value: 3 }, Counter { value: 3 }]
+    );
+
+    conflict_once();
+    let (_, attempts) = db
+        .entity("a")
+        .detach_with_attempts::<Counter>()
+        .await
+        .unwrap();
+    assert_eq!(attempts, 2);
+    conflict_once();
+    let (_, attempts) = db.entity("b").delete_with_attempts().await.unwrap();
+    assert_eq!(attempts, 2);
+    assert_eq!(db.entity("b").get::<Counter>().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn concurrent_increments_are_not_lost() {
+    let db = DB::new_in_memory().with_retry_policy(fast_retry(100));
+    db.entity("counter")
+        .attach(Counter { value: 0 })
+        .await
+        .unwrap();
+
+    let tasks = (0..10)
+        .map(|_| {
+            let db = db.clone();
+            tokio::spawn(async move { increment(&db, 0).await })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    let counter = db.entity("counter").get::<Counter>().await.unwrap();
+    assert_eq!(counter, Some(Counter { value: 10 }));
+}
@@ -0,0 +1,141 @@
+use kv_entity::{DB, Error, MemoryBackend, StorageBackend};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Doc {
+    #[prost(string, tag = "1")]
+    pub body: String,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Cites {
+    #[prost(int32, tag = "1")]
+    pub page: i32,
+}
+
+fn doc(body: &str) -> Doc {
+    Doc {
+        body: body.to_string(),
+    }
+}
+
+fn assert_mismatch<T>(result: Result<T, Error>, expected: u64, actual: u64) {
+    match result {
+        Err(Error::RevisionMismatch {
+            expected: e,
+            actual: a,
+        }) => assert_eq!((e, a), (expected, actual)),
+        Err(error) => panic!("expected revision mismatch, got {error:?}"),
+        Ok(_) => panic!("expected revision mismatch"),
+    }
+}
+
+#[tokio::test]
+async fn every_write_bumps_the_revision() {
+    let db = DB::new_in_memory();
+    let entity = db.entity("1");
+    assert_eq!(entity.revision().await.unwrap(), 0);
+
+    entity.attach(doc("a")).await.unwrap();
+    assert_eq!(entity.revision().await.unwrap(), 1);
+    db.entity("2").attach(doc("b")).await.unwrap();
+    entity.link("2", Cites { page: 1 }).await.unwrap();
+    assert_eq!(entity.revision().await.unwrap(), 2);
+    entity
+        .update_relation::<Cites>("2", |cites| cites.page = 2)
+        .await
+        .unwrap();
+    assert_eq!(entity.revision().await.unwrap(), 3);
+    entity.unlink::<Cites>("2").await.unwrap();
+    assert_eq!(entity.revision().await.unwrap(), 4);
+    entity.detach::<Doc>().await.unwrap();
+    assert_eq!(entity.revision().await.unwrap(), 5);
+}
+
+#[tokio::test]
+async fn conditional_writes_check_the_revision() {
+    let db = DB::new_in_memory();
+    let entity = db.entity("1");
+    entity.if_revision(0).insert(doc("a")).await.unwrap();
+    assert_mismatch(entity.if_revision(0).attach(doc("b")).await, 0, 1);
+
+    entity.if_revision(1).attach(doc("b")).await.unwrap();
+    assert_mismatch(entity.if_revision(1).detach::<Doc>().await, 1, 2);
+    assert_eq!(entity.get::<Doc>().await.unwrap(), Some(doc("b")));
+    entity.if_revision(2).delete().await.unwrap();
+}
+
+#[tokio::test]
+async fn links_do_not_create_metadata_for_missing_entities() {
+    let db = DB::new_in_memory();
+    db.entity("1").link("2", Cites { page: 1 }).await.unwrap();
+    db.entity("1")
+        .update_relation::<Cites>("2", |cites| cites.page = 2)
+        .await
+        .unwrap();
+    db.entity("1").unlink::<Cites>("2").await.unwrap();
+
+    for id in ["1", "2"] {
+        assert!(matches!(
+            db.entity(id).metadata().await,
+            Err(Error::NotFound)
+        ));
+        assert_eq!(db.entity(id).revision().await.unwrap(), 0);
+    }
+    assert!(matches!(
+        db.entity("1").delete().await,
+        Err(Error::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn revisions_are_not_reused_after_delete() {
+    let db = DB::new_in_memory();
+    let entity = db.entity("1");
+    entity.attach(doc("a")).await.unwrap();
+    entity.attach(doc("b")).await.unwrap();
+    let stale = entity.revision().await.unwrap();
+    assert_eq!(stale, 2);
+
+    entity.delete().await.unwrap();
+    assert_eq!(entity.revision().await.unwrap(), 0);
+    entity.attach(doc("c")).await.unwrap();
+    entity.attach(doc("d")).await.unwrap();
+
+    // 重新创建后的版本号不会回到已经出现过的值
+    let revision = entity.revision().await.unwrap();
+    assert!(revision > stale, "{revision}");
+    assert_mismatch(
+        entity.if_revision(stale).attach(doc("e")).await,
+        stale,
+        revision,
+    );
+    assert_eq!(entity.get::<Doc>().await.unwrap(), Some(doc("d")));
+
+    // 再次删除和创建时继续递增
+    entity.delete().await.unwrap();
+    entity.attach(doc("f")).await.unwrap();
+    assert!(entity.revision().await.unwrap() > revision);
+}
+
+#[tokio::test]
+async fn revision_floor_is_removed_after_recreate() {
+    let backend = MemoryBackend::new();
+    let db = DB::with_backend(backend.clone());
+    let entity = db.entity("1");
+    let floor = async || {
+        let ts = backend.current_timestamp().await.unwrap();
+        backend
+            .snapshot(ts)
+            .get("entity/revision/e-1".to_string().into())
+            .await
+            .unwrap()
+    };
+
+    entity.attach(doc("a")).await.unwrap();
+    entity.delete().await.unwrap();
+    assert_eq!(floor().await, Some(b"2".to_vec()));
+
+    entity.attach(doc("b")).await.unwrap();
+    assert_eq!(floor().await, None);
+    assert_eq!(entity.revision().await.unwrap(), 3);
+}
@@ -0,0 +1,115 @@
+use kv_entity::{DB, EntityID, Error, KvComponent, MemoryBackend, StorageBackend};
+use prost::Message;
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Account {
+    #[index(unique)]
+    #[prost(string, tag = "1")]
+    pub email: String,
+    #[index]
+    #[prost(int32, tag = "2")]
+    pub level: i32,
+}
+
+fn account(email: &str, level: i32) -> Account {
+    Account {
+        email: email.to_string(),
+        level,
+    }
+}
+
+// 绕过索引直接写入组件数据，模拟旧版本组件定义写入的数据
+async fn put_raw(backend: &MemoryBackend, entity: &str, value: Account) {
+    let mut txn = backend.begin_optimistic().await.unwrap();
+    let key = format!("component/single/{}/{}", Account::type_path().0, entity);
+    txn.put(key.into(), value.encode_to_vec()).await.unwrap();
+    txn.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn sync_builds_index_for_existing_data() {
+    let backend = MemoryBackend::new();
+    let db = DB::with_backend(backend.clone());
+    put_raw(&backend, "e-a", account("a@example.com", 1)).await;
+    put_raw(&backend, "resource", account("r@example.com", 1)).await;
+
+    let drifts = db.sync_indexes().await.unwrap();
+    assert_eq!(drifts.len(), 1);
+    assert_eq!(drifts[0].added, vec!["email", "level"]);
+    assert_eq!(drifts[0].unique_added, vec!["email"]);
+    assert!(db.index_drift().await.unwrap().is_empty());
+
+    assert_eq!(db.query::<Account>().level(1).count().await.unwrap(), 2);
+    let found = db
+        .query::<Account>()
+        .email("r@example.com")
+        .entity()
+        .await
+        .unwrap();
+    assert_eq!(found.entity_id(), &EntityID::resource());
+
+    // 资源实体的唯一索引归属正确，可以正常更新
+    db.resource()
+        .await
+        .attach(account("r@example.com", 2))
+        .await
+        .unwrap();
+    assert_eq!(db.query::<Account>().level(2).count().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn unique_violation_leaves_index_untouched() {
+    let backend = MemoryBackend::new();
+    let db = DB::with_backend(backend.clone());
+    // 第一页之后才出现重复值
+    for i in 0..200 {
+        put_raw(
+            &backend,
+            &format!("e-{i:03}"),
+            account(&format!("{i}@example.com"), 1),
+        )
+        .await;
+    }
+    put_raw(&backend, "e-999", account("0@example.com", 1)).await;
+
+    let result = db.sync_indexes().await;
+    assert!(
+        matches!(result, Err(Error::UniqueViolation { ref field, .. }) if field == "email"),
+        "{result:?}"
+    );
+    assert_eq!(db.query::<Account>().level(1).count().await.unwrap(), 0);
+    assert_eq!(db.index_drift().await.unwrap().len(), 1);
+
+    // 修正数据后可以正常重建
+    put_raw(&backend, "e-999", account("999@example.com", 1)).await;
+    db.sync_indexes().await.unwrap();
+    assert_eq!(db.query::<Account>().level(1).count().await.unwrap(), 201);
+}
+
+#[tokio::test]
+async fn rebuild_checks_unique_keys_claimed_after_the_precheck() {
+    let backend = MemoryBackend::new();
+    let db = DB::with_backend(backend.clone());
+    put_raw(&backend, "e-a", account("a@example.com", 1)).await;
+    put_raw(&backend, "e-b", account("b@example.com", 1)).await;
+    // 模拟整体校验之后，并发的写入方已经占用了 e-b 的唯一值
+    let mut txn = backend.begin_optimistic().await.unwrap();
+    let key = format!(
+        "component/index/{}/email.unique/b@example.com",
+        Account::type_path().0
+    );
+    txn.put(key.into(), b"e-x".to_vec()).await.unwrap();
+    txn.commit().await.unwrap();
+
+    let result = db.sync_indexes().await;
+    assert!(
+        matches!(
+            result,
+            Err(Error::UniqueViolation { ref existing_entity, .. })
+                if *existing_entity == EntityID::new("x".to_string())
+        ),
+        "{result:?}"
+    );
+    // 索引记录保持不变，下次同步会重新检测到变化
+    assert_eq!(db.index_drift().await.unwrap().len(), 1);
+}
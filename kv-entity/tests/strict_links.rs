@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use kv_entity::{DB, Error, RelationDirection};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Person {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Knows {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+async fn setup(strict: bool) -> DB {
+    let db = DB::new_in_memory().with_strict_links(strict);
+    for name in ["a", "b"] {
+        db.entity(name)
+            .attach(Person {
+                name: name.to_string(),
+            })
+            .await
+            .unwrap();
+    }
+    db
+}
+
+async fn has_edges(db: &DB, entity: &str) -> bool {
+    !db.entity(entity)
+        .edges_entity::<Knows>(RelationDirection::Both)
+        .await
+        .unwrap()
+        .is_empty()
+}
+
+#[tokio::test]
+async fn strict_links_require_both_endpoints() {
+    let db = setup(true).await;
+    assert!(matches!(
+        db.entity("a").link("x", Knows { since: 1 }).await,
+        Err(Error::NotFound)
+    ));
+    assert!(matches!(
+        db.entity("x").link("a", Knows { since: 1 }).await,
+        Err(Error::NotFound)
+    ));
+    assert!(!has_edges(&db, "a").await);
+    db.entity("a").link("b", Knows { since: 1 }).await.unwrap();
+
+    let db = setup(false).await;
+    db.entity("a").link("x", Knows { since: 1 }).await.unwrap();
+}
+
+#[tokio::test]
+async fn link_conflicts_with_concurrent_delete_of_either_end() {
+    for deleted in ["a", "b"] {
+        let db = setup(true).await;
+        let first = AtomicBool::new(true);
+        // 第一次执行时在 link 读取两端之后、提交之前删除其中一端
+        let result = db
+            .transaction(|txn| {
+                let (db, first) = (db.clone(), &first);
+                async move {
+                    txn.entity("a").link("b", Knows { since: 1 }).await?;
+                    if first.swap(false, Ordering::SeqCst) {
+                        db.entity(deleted).delete().await?;
+                    }
+                    Ok(())
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(Error::NotFound)), "{deleted}");
+        assert!(!has_edges(&db, "a").await, "{deleted}");
+        assert!(!has_edges(&db, "b").await, "{deleted}");
+    }
+}
+
+#[tokio::test]
+async fn delete_conflicts_with_concurrent_link() {
+    for deleted in ["a", "b"] {
+        let db = setup(true).await;
+        let first = AtomicBool::new(true);
+        // 第一次执行时在删除读取边之后、提交之前建立关系
+        db.transaction(|txn| {
+            let (db, first) = (db.clone(), &first);
+            async move {
+                txn.entity(deleted).delete().await?;
+                if first.swap(false, Ordering::SeqCst) {
+                    db.entity("a").link("b", Knows { since: 1 }).await?;
+                }
+                Ok(())
+            }
+        })
+        .await
+        .unwrap();
+        // 重试后的删除看到了新建的关系并一并删除，不会留下指向已删除实体的边
+        assert!(!has_edges(&db, "a").await, "{deleted}");
+        assert!(!has_edges(&db, "b").await, "{deleted}");
+    }
+}
@@ -0,0 +1,139 @@
+use futures::TryStreamExt;
+use kv_entity::{DB, EntityID, KvRelation, RelationDirection};
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[kv_relation(symmetric)]
+pub struct Sibling {
+    #[index]
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Likes {
+    #[prost(int32, tag = "1")]
+    pub weight: i32,
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+async fn edges<T: KvRelation + prost::Message + Default + 'static>(
+    db: &DB,
+    entity: &str,
+    direction: RelationDirection,
+) -> Vec<(EntityID, T)> {
+    db.entity(entity)
+        .edges::<T>(direction)
+        .await
+        .map_ok(|(entity_id, _, value)| (entity_id, value))
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn symmetric_relation_is_shared_by_both_ends() {
+    assert!(Sibling::symmetric());
+    assert!(!Likes::symmetric());
+
+    let db = DB::new_in_memory();
+    db.entity("b")
+        .link("a", Sibling { since: 1 })
+        .await
+        .unwrap();
+    for (from, to) in [("a", "b"), ("b", "a")] {
+        assert_eq!(
+            db.entity(from).relation::<Sibling>(to).await.unwrap(),
+            Some(Sibling { since: 1 })
+        );
+    }
+
+    // 从另一端关联只更新同一份数据
+    db.entity("a")
+        .link("b", Sibling { since: 2 })
+        .await
+        .unwrap();
+    assert_eq!(
+        db.query_relation::<Sibling>()
+            .since_range(..)
+            .count()
+            .await
+            .unwrap(),
+        1
+    );
+    for direction in [RelationDirection::In, RelationDirection::Out] {
+        assert_eq!(
+            edges::<Sibling>(&db, "a", direction).await,
+            [(id("b"), Sibling { since: 2 })]
+        );
+    }
+    let (_, direction, _) = db
+        .entity("b")
+        .edges::<Sibling>(RelationDirection::In)
+        .await
+        .try_next()
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(direction, RelationDirection::Both));
+
+    db.entity("a")
+        .update_relation::<Sibling>("b", |sibling| sibling.since = 3)
+        .await
+        .unwrap();
+    assert_eq!(
+        db.entity("b").relation::<Sibling>("a").await.unwrap(),
+        Some(Sibling { since: 3 })
+    );
+
+    // 从任意一端解除关联
+    db.entity("a").unlink::<Sibling>("b").await.unwrap();
+    assert!(!db.entity("b").has_relation::<Sibling>("a").await.unwrap());
+    assert!(
+        edges::<Sibling>(&db, "b", RelationDirection::Both)
+            .await
+            .is_empty()
+    );
+    assert_eq!(
+        db.query_relation::<Sibling>()
+            .since_range(..)
+            .count()
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn edges_follow_the_key_order() {
+    let db = DB::new_in_memory();
+    let (mut linked, mut linking) = (Vec::new(), Vec::new());
+    for i in 1..=20 {
+        let other = format!("n{i:02}");
+        db.entity("hub")
+            .link(other.clone(), Likes { weight: i })
+            .await
+            .unwrap();
+        db.entity(other.clone())
+            .link("hub", Likes { weight: -i })
+            .await
+            .unwrap();
+        linked.push((id(&other), Likes { weight: i }));
+        linking.push((id(&other), Likes { weight: -i }));
+    }
+    assert_eq!(
+        edges::<Likes>(&db, "hub", RelationDirection::In).await,
+        linked
+    );
+    assert_eq!(
+        edges::<Likes>(&db, "hub", RelationDirection::Out).await,
+        linking
+    );
+    // Both 先返回 in 方向的边，再返回 out 方向的边
+    assert_eq!(
+        edges::<Likes>(&db, "hub", RelationDirection::Both).await,
+        [linked, linking].concat()
+    );
+}
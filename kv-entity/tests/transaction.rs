@@ -0,0 +1,103 @@
+use kv_entity::{DB, Error, RelationDirection};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Profile {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Badge {
+    #[prost(int32, tag = "1")]
+    pub level: i32,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Follows {
+    #[prost(int32, tag = "1")]
+    pub weight: i32,
+}
+
+fn profile(name: &str) -> Profile {
+    Profile {
+        name: name.to_string(),
+    }
+}
+
+async fn setup() -> DB {
+    let db = DB::new_in_memory();
+    db.entity("b").attach(profile("b")).await.unwrap();
+    db.entity("c")
+        .attach((profile("c"), Badge { level: 1 }))
+        .await
+        .unwrap();
+    db
+}
+
+#[tokio::test]
+async fn commits_all_operations_together() {
+    let db = setup().await;
+    db.transaction(|tx| async move {
+        tx.entity("a").attach(profile("a")).await?;
+        tx.entity("a").link("b", Follows { weight: 1 }).await?;
+        tx.entity("c").detach::<Badge>().await?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        db.entity("a").get::<Profile>().await.unwrap(),
+        Some(profile("a"))
+    );
+    let edges = db
+        .entity("a")
+        .edges_entity::<Follows>(RelationDirection::In)
+        .await
+        .unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(db.entity("c").get::<Badge>().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn error_rolls_back_every_operation() {
+    let db = setup().await;
+    let result: Result<(), Error> = db
+        .transaction(|tx| async move {
+            tx.entity("a").attach(profile("a")).await?;
+            tx.entity("a").link("b", Follows { weight: 1 }).await?;
+            tx.entity("c").delete().await?;
+            Err(Error::NotFound)
+        })
+        .await;
+    assert!(matches!(result, Err(Error::NotFound)));
+
+    assert_eq!(db.entity("a").get::<Profile>().await.unwrap(), None);
+    assert_eq!(db.query::<Profile>().name("a").count().await.unwrap(), 0);
+    let edges = db
+        .entity("b")
+        .edges_entity::<Follows>(RelationDirection::Out)
+        .await
+        .unwrap();
+    assert!(edges.is_empty());
+    assert_eq!(
+        db.entity("c").get::<Badge>().await.unwrap(),
+        Some(Badge { level: 1 })
+    );
+}
+
+#[tokio::test]
+async fn reads_see_uncommitted_writes_of_same_transaction() {
+    let db = setup().await;
+    let seen = db
+        .transaction(|tx| async move {
+            tx.entity("a").attach(profile("a")).await?;
+            let seen = tx.entity("a").get::<Profile>().await?;
+            tx.entity("c").delete().await?;
+            Ok((seen, tx.entity("c").get::<Badge>().await?))
+        })
+        .await
+        .unwrap();
+    assert_eq!(seen, (Some(profile("a")), None));
+}
@@ -0,0 +1,162 @@
+use futures::TryStreamExt;
+use kv_entity::{DB, EntityID, Error, RelationDirection};
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Friend {
+    #[prost(int32, tag = "1")]
+    pub since: i32,
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+// a -> b -> c -> d -> a，另有 a -> c 的捷径和 d -> e
+async fn setup() -> DB {
+    let db = DB::new_in_memory();
+    for (from, to) in [
+        ("a", "b"),
+        ("b", "c"),
+        ("c", "d"),
+        ("d", "a"),
+        ("a", "c"),
+        ("d", "e"),
+    ] {
+        db.entity(from).link(to, Friend { since: 1 }).await.unwrap();
+    }
+    db
+}
+
+async fn reached(
+    db: &DB,
+    start: &str,
+    depth: impl std::ops::RangeBounds<usize>,
+) -> Vec<(EntityID, usize)> {
+    let mut reached = db
+        .entity(start)
+        .traverse::<Friend>(RelationDirection::In)
+        .depth(depth)
+        .stream()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    reached.sort();
+    reached
+}
+
+#[tokio::test]
+async fn traverse_returns_each_entity_once_at_its_depth() {
+    let db = setup().await;
+    assert_eq!(reached(&db, "a", 1..=1).await, [(id("b"), 1), (id("c"), 1)]);
+    assert_eq!(
+        reached(&db, "a", 0..).await,
+        [
+            (id("a"), 0),
+            (id("b"), 1),
+            (id("c"), 1),
+            (id("d"), 2),
+            (id("e"), 3)
+        ]
+    );
+    assert_eq!(reached(&db, "a", 2..3).await, [(id("d"), 2)]);
+
+    // 沿反方向遍历
+    let mut back = db
+        .entity("a")
+        .traverse::<Friend>(RelationDirection::Out)
+        .depth(1..=2)
+        .stream()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    back.sort();
+    assert_eq!(back, [(id("c"), 2), (id("d"), 1)]);
+}
+
+#[tokio::test]
+async fn empty_depth_range_is_an_error() {
+    let db = setup().await;
+    #[allow(clippy::reversed_empty_ranges)]
+    for traversal in [
+        db.entity("a")
+            .traverse::<Friend>(RelationDirection::In)
+            .depth(0..0),
+        db.entity("a")
+            .traverse::<Friend>(RelationDirection::In)
+            .depth(..0),
+        db.entity("a")
+            .traverse::<Friend>(RelationDirection::In)
+            .depth(3..=2),
+    ] {
+        assert!(matches!(
+            traversal.path_to("b").await,
+            Err(Error::InvalidDepth(_))
+        ));
+        assert!(matches!(
+            traversal.stream().try_collect::<Vec<_>>().await,
+            Err(Error::InvalidDepth(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn frontier_is_capped() {
+    let db = DB::new_in_memory();
+    for i in 0..5 {
+        db.entity("hub")
+            .link(format!("n{i}"), Friend { since: i })
+            .await
+            .unwrap();
+    }
+    let result = db
+        .entity("hub")
+        .traverse::<Friend>(RelationDirection::In)
+        .max_frontier(4)
+        .stream()
+        .try_collect::<Vec<_>>()
+        .await;
+    assert!(matches!(result, Err(Error::TraversalLimitExceeded(4))));
+
+    let neighbors = db
+        .entity("hub")
+        .traverse::<Friend>(RelationDirection::In)
+        .max_frontier(5)
+        .stream()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(neighbors.len(), 5);
+}
+
+#[tokio::test]
+async fn shortest_path_ignores_direction_and_respects_max_depth() {
+    let db = setup().await;
+    assert_eq!(
+        db.shortest_path::<Friend>("a", "d", 3).await.unwrap(),
+        Some(vec![id("a"), id("d")])
+    );
+    assert_eq!(
+        db.shortest_path::<Friend>("b", "e", 3).await.unwrap(),
+        Some(vec![id("b"), id("c"), id("d"), id("e")])
+    );
+    assert_eq!(db.shortest_path::<Friend>("b", "e", 2).await.unwrap(), None);
+    assert_eq!(
+        db.shortest_path::<Friend>("a", "z", 10).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        db.shortest_path::<Friend>("a", "a", 0).await.unwrap(),
+        Some(vec![id("a")])
+    );
+
+    // 有向遍历不会沿反方向走捷径
+    assert_eq!(
+        db.entity("b")
+            .traverse::<Friend>(RelationDirection::In)
+            .depth(..=5)
+            .path_to("a")
+            .await
+            .unwrap(),
+        Some(vec![id("b"), id("c"), id("d"), id("a")])
+    );
+}
@@ -0,0 +1,132 @@
+use kv_entity::{DB, EntityID, Error};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct User {
+    #[index(unique)]
+    #[prost(string, tag = "1")]
+    pub email: String,
+    #[index]
+    #[prost(string, tag = "2")]
+    pub name: String,
+}
+
+fn user(email: &str, name: &str) -> User {
+    User {
+        email: email.to_string(),
+        name: name.to_string(),
+    }
+}
+
+fn assert_violation(result: Result<kv_entity::EntityHandler, Error>, owner: &str) {
+    match result {
+        Err(Error::UniqueViolation {
+            field,
+            value,
+            existing_entity,
+        }) => {
+            assert_eq!(field, "email");
+            assert_eq!(value, "a@example.com");
+            assert_eq!(existing_entity, EntityID::new(owner.to_string()));
+        }
+        other => panic!("expected unique violation, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn duplicate_value_is_rejected() {
+    let db = DB::new_in_memory();
+    db.entity("1")
+        .attach(user("a@example.com", "a"))
+        .await
+        .unwrap();
+
+    assert_violation(db.entity("2").attach(user("a@example.com", "b")).await, "1");
+    assert_eq!(db.entity("2").get::<User>().await.unwrap(), None);
+    // 非唯一字段可以重复
+    db.entity("2")
+        .attach(user("b@example.com", "a"))
+        .await
+        .unwrap();
+    assert_eq!(db.query::<User>().name("a").count().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn owner_can_rewrite_its_own_value() {
+    let db = DB::new_in_memory();
+    db.entity("1")
+        .attach(user("a@example.com", "a"))
+        .await
+        .unwrap();
+    db.entity("1")
+        .attach(user("a@example.com", "renamed"))
+        .await
+        .unwrap();
+    assert_eq!(
+        db.query::<User>()
+            .email("a@example.com")
+            .single()
+            .await
+            .unwrap()
+            .name,
+        "renamed"
+    );
+}
+
+#[tokio::test]
+async fn value_is_released_by_update_detach_and_delete() {
+    let db = DB::new_in_memory();
+
+    db.entity("1")
+        .attach(user("a@example.com", "a"))
+        .await
+        .unwrap();
+    db.entity("1")
+        .attach(user("c@example.com", "a"))
+        .await
+        .unwrap();
+    db.entity("2")
+        .attach(user("a@example.com", "b"))
+        .await
+        .unwrap();
+
+    db.entity("2").detach::<User>().await.unwrap();
+    db.entity("3")
+        .attach(user("a@example.com", "c"))
+        .await
+        .unwrap();
+
+    db.entity("3").delete().await.unwrap();
+    db.entity("4")
+        .attach(user("a@example.com", "d"))
+        .await
+        .unwrap();
+    assert_violation(db.entity("5").attach(user("a@example.com", "e")).await, "4");
+}
+
+#[tokio::test]
+async fn concurrent_claims_have_one_winner() {
+    let db = DB::new_in_memory();
+    let tasks = (0..8).map(|i| {
+        let db = db.clone();
+        tokio::spawn(async move {
+            db.entity(i.to_string())
+                .attach(user("a@example.com", "x"))
+                .await
+                .is_ok()
+        })
+    });
+    let winners = futures::future::join_all(tasks)
+        .await
+        .into_iter()
+        .filter(|result| *result.as_ref().unwrap())
+        .count();
+    assert_eq!(winners, 1);
+    assert_eq!(
+        db.query::<User>()
+            .email("a@example.com")
+            .count()
+            .await
+            .unwrap(),
+        1
+    );
+}
@@ -0,0 +1,226 @@
+use futures::TryStreamExt;
+use kv_entity::{DB, EntityID, RelationDirection};
+
+#[derive(kv_entity::KvComponent, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Account {
+    #[index]
+    #[prost(string, tag = "1")]
+    pub owner: String,
+    #[index]
+    #[prost(int64, tag = "2")]
+    pub balance: i64,
+}
+
+#[derive(kv_entity::KvRelation, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct Transfer {
+    #[index]
+    #[prost(int64, tag = "1")]
+    pub amount: i64,
+}
+
+fn account(owner: &str, balance: i64) -> Account {
+    Account {
+        owner: owner.to_string(),
+        balance,
+    }
+}
+
+fn id(id: &str) -> EntityID {
+    EntityID::new(id.to_string())
+}
+
+#[tokio::test]
+async fn view_reads_the_past_state() {
+    let db = DB::new_in_memory();
+    db.entity("1").attach(account("alice", 100)).await.unwrap();
+    db.entity("2").attach(account("bob", 50)).await.unwrap();
+    let past = db.read_session().await.unwrap().timestamp().clone();
+
+    db.entity("1").attach(account("alice", 70)).await.unwrap();
+    db.entity("1")
+        .link("2", Transfer { amount: 30 })
+        .await
+        .unwrap();
+    db.entity("3").attach(account("carol", 10)).await.unwrap();
+    db.entity("2").delete().await.unwrap();
+
+    let view = db.at(past);
+    assert_eq!(
+        view.entity("1").get::<Account>().await.unwrap(),
+        Some(account("alice", 100))
+    );
+    assert_eq!(
+        view.entity("2").get::<Account>().await.unwrap(),
+        Some(account("bob", 50))
+    );
+    assert_eq!(view.entity("1").revision().await.unwrap(), 1);
+    assert!(
+        view.entity("1")
+            .edges_entity::<Transfer>(RelationDirection::In)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let owners = view
+        .get::<Account>()
+        .map_ok(|(_, account)| account.owner)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(owners, ["alice", "bob"]);
+    assert_eq!(
+        view.query_relation::<Transfer>()
+            .amount_range(..)
+            .count()
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn view_filters_use_the_past_index() {
+    let db = DB::new_in_memory();
+    db.entity("1").attach(account("alice", 100)).await.unwrap();
+    db.entity("2").attach(account("bob", 50)).await.unwrap();
+    let past = db.read_session().await.unwrap().timestamp().clone();
+    db.entity("1").attach(account("alice", 10)).await.unwrap();
+
+    let view = db.at(past);
+    let rich = view.query::<Account>().balance_gt(60);
+    assert_eq!(rich.count().await.unwrap(), 1);
+    assert_eq!(rich.single().await.unwrap(), account("alice", 100));
+    assert_eq!(
+        db.query::<Account>().balance_gt(60).count().await.unwrap(),
+        0
+    );
+
+    let entity = view
+        .query::<Account>()
+        .owner("alice")
+        .entity()
+        .await
+        .unwrap();
+    assert_eq!(entity.entity_id(), &id("1"));
+    assert_eq!(
+        entity.get::<Account>().await.unwrap(),
+        Some(account("alice", 100))
+    );
+
+    let either = view
+        .query::<Account>()
+        .owner("bob")
+        .or(view.query::<Account>().balance(100))
+        .limit(5);
+    let list = either.list().await.unwrap();
+    assert_eq!(list.entity_ids(), [id("1"), id("2")]);
+    assert_eq!(
+        list.get::<Account>().await.unwrap(),
+        [account("alice", 100), account("bob", 50)]
+    );
+    for entity in list.iter() {
+        assert_eq!(entity.revision().await.unwrap(), 1);
+    }
+
+    let page = view
+        .query::<Account>()
+        .balance_range(..)
+        .limit(1)
+        .page()
+        .await
+        .unwrap();
+    let rest = view
+        .query::<Account>()
+        .balance_range(..)
+        .after(page.cursor.unwrap())
+        .stream()
+        .map_ok(|(_, account)| account.balance)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(rest, [100]);
+}
+
+#[tokio::test]
+async fn session_is_isolated_from_later_writes() {
+    let db = DB::new_in_memory();
+    db.entity("1").attach(account("alice", 100)).await.unwrap();
+    db.entity("2").attach(account("bob", 50)).await.unwrap();
+    db.entity("1")
+        .link("2", Transfer { amount: 5 })
+        .await
+        .unwrap();
+
+    let session = db.read_session().await.unwrap();
+
+    // 会话开始后的写入对会话不可见
+    db.entity("1").attach(account("alice", 0)).await.unwrap();
+    db.entity("1")
+        .update_relation::<Transfer>("2", |transfer| transfer.amount = 100)
+        .await
+        .unwrap();
+    db.entity("3").attach(account("carol", 10)).await.unwrap();
+    db.entity("2").detach::<Account>().await.unwrap();
+
+    assert_eq!(
+        session.entity("1").get::<Account>().await.unwrap(),
+        Some(account("alice", 100))
+    );
+    assert_eq!(
+        session
+            .query::<Account>()
+            .balance_range(..)
+            .all()
+            .await
+            .unwrap(),
+        [account("bob", 50), account("alice", 100)]
+    );
+    let edges = session
+        .entity("1")
+        .edges::<Transfer>(RelationDirection::In)
+        .await
+        .map_ok(|(entity_id, _, transfer)| (entity_id, transfer))
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(edges, [(id("2"), Transfer { amount: 5 })]);
+    assert_eq!(
+        session.entity("1").relation::<Transfer>("2").await.unwrap(),
+        Some(Transfer { amount: 5 })
+    );
+    assert_eq!(
+        session
+            .entity("1")
+            .degree::<Transfer>(RelationDirection::In)
+            .await
+            .unwrap(),
+        1
+    );
+
+    let list = session.get_entity::<Account>().await.unwrap();
+    assert_eq!(list.entity_ids(), [id("1"), id("2")]);
+    let streamed = list
+        .stream::<Account>()
+        .map_ok(|(_, account)| account.map(|account| account.balance))
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(streamed, [Some(100), Some(50)]);
+
+    let archetype = session
+        .query_archetype::<(kv_entity::With<Account>,)>()
+        .stream()
+        .map_ok(|(entity_id, _)| entity_id)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(archetype, [id("1"), id("2")]);
+
+    // 新的会话看到最新的状态
+    let latest = db.read_session().await.unwrap();
+    assert_eq!(
+        latest.get_entity::<Account>().await.unwrap().entity_ids(),
+        [id("1"), id("3")]
+    );
+}